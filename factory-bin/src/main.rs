@@ -1,5 +1,3 @@
-use std::path::PathBuf;
-
 use common::AppResult;
 use factory_lib::data::DataSet;
 use factory_lib::domain::CraftingGraph;
@@ -30,6 +28,9 @@ fn main() -> AppResult<()> {
     println!("Reading dataset");
 
     let data = DataSet::from_file("recipe-lister/recipe.json", &natural_items)?;
+    for name in data.demoted_naturals() {
+        println!("Natural item {name} is produced by a recipe, treating it as crafted.");
+    }
 
     println!("Parsing crafting graph");
     let recipe_graph = CraftingGraph::from_dataset(&data);
@@ -48,7 +49,11 @@ fn main() -> AppResult<()> {
         .expect("Should be ok");
 
     for (idx, possibility) in crafting_possibilities.into_iter().enumerate().rev() {
-        println!("{}. with {} nodes", idx + 1, possibility.data.node_count());
+        println!(
+            "{}. with {} nodes",
+            idx + 1,
+            possibility.graph().node_count()
+        );
         // let file_name: PathBuf = format!("outputs/{idx}.svg").into();
         // possibility.save_as_svg(file_name)?;
     }
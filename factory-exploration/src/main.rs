@@ -27,6 +27,9 @@ fn main() -> Result<()> {
     .collect();
 
     let mut data = DataSet::from_file("recipe-lister/recipe.json", &natural_items)?;
+    for name in data.demoted_naturals() {
+        println!("Natural item {name} is produced by a recipe, treating it as crafted.");
+    }
 
     let recipe_filters = [
         "iron-plate",
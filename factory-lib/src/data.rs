@@ -1,22 +1,44 @@
 use crate::{
-    entities::{Item, Recipe},
+    entities::{FactoryKind, Item, Recipe},
     error::{FactoryError, FactoryResult},
-    traits,
+    traits::{self, DataSource as _},
 };
 use itertools::Itertools as _;
-use rust_decimal::{prelude::FromPrimitive as _, Decimal};
-use serde::{Deserialize, Serialize};
+use rust_decimal::Decimal;
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct RecipeJson {
     name: String,
     ingredients: IngredientField,
     category: String,
-    products: Vec<ItemJson>,
+    #[serde(flatten)]
+    products: ProductField,
     #[serde(rename = "energy")]
     time: f64,
+    #[serde(default)]
+    group: Option<RecipeGroupJson>,
+    #[serde(default)]
+    allow_productivity: Option<bool>,
+    #[serde(default, alias = "reverse_recipe")]
+    allow_decomposition: bool,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecipeGroupJson {
+    name: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -26,15 +48,253 @@ pub enum IngredientField {
     Empty {}, // If recipe doesn't contain any engredients in JSON
 }
 
+/// Recipe dumps list the products either as `products` (recipe lister) or `results` (raw game data)
+/// array, or as a single `result` with an optional `result_count`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(untagged)]
+pub enum ProductField {
+    Products {
+        products: Vec<ItemJson>,
+    },
+    Results {
+        results: Vec<ItemJson>,
+    },
+    Single {
+        result: String,
+        #[serde(default = "ProductField::default_result_count")]
+        result_count: usize,
+    },
+}
+
+impl ProductField {
+    fn default_result_count() -> usize {
+        1
+    }
+
+    fn into_items(self) -> Vec<ItemJson> {
+        match self {
+            ProductField::Products { products: items }
+            | ProductField::Results { results: items } => items,
+            ProductField::Single {
+                result,
+                result_count,
+            } => vec![ItemJson {
+                name: result,
+                amount: AmountJson(result_count.to_string()),
+                catalyst_amount: None,
+                probability: None,
+            }],
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ItemJson {
     name: String,
-    amount: usize,
+    amount: AmountJson,
+    /// Part of `amount` which is returned catalyst, encoded like `amount`.
+    #[serde(default)]
+    catalyst_amount: Option<AmountJson>,
+    /// Chance of a product being returned by a craft, like uranium-235 from uranium processing.
+    #[serde(default)]
+    probability: Option<AmountJson>,
+}
+
+impl ItemJson {
+    /// Amount returned by a craft on average, i.e. scaled by the `probability` of the product.
+    fn expected_amount(&self) -> FactoryResult<Decimal> {
+        let amount = self.amount.to_decimal()?;
+
+        match &self.probability {
+            Some(probability) => Ok(amount * probability.to_decimal()?),
+            None => Ok(amount),
+        }
+    }
 }
 
+/// Item amount as written in the dump. Exports disagree on the encoding, so integers, floats,
+/// numeric strings (`"0.5"`) and fraction strings (`"1/2"`) are all accepted and kept verbatim
+/// until [`AmountJson::to_decimal`] turns them into an exact [`Decimal`].
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AmountJson(String);
+
+impl AmountJson {
+    /// Returns [`FactoryError::InvalidAmount`] if the amount isn't a number or a fraction of two
+    /// numbers.
+    fn to_decimal(&self) -> FactoryResult<Decimal> {
+        let invalid = || FactoryError::InvalidAmount(self.0.clone());
+        let parse = |number: &str| {
+            Decimal::from_str_exact(number.trim())
+                .or_else(|_| Decimal::from_scientific(number.trim()))
+                .map_err(|_| invalid())
+        };
+
+        match self.0.split_once('/') {
+            Some((numerator, denominator)) => parse(numerator)?
+                .checked_div(parse(denominator)?)
+                .ok_or_else(invalid),
+            None => parse(&self.0),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AmountJson {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct AmountVisitor;
+
+        impl Visitor<'_> for AmountVisitor {
+            type Value = AmountJson;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number or a numeric string")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(AmountJson(value.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(AmountJson(value.to_string()))
+            }
+
+            // Display of floats is the shortest string which parses back to the same float, so
+            // `0.1` stays `0.1` instead of its binary approximation.
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+                Ok(AmountJson(value.to_string()))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(AmountJson(value.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+/// Recipes and items parsed from a recipe dump.
+/// Parsing rejects recipes whose crafting time (`energy`) is zero or negative with
+/// [`FactoryError::NonPositiveRecipeTime`], so every recipe in the data set has a finite, positive
+/// crafting rate.
+/// Recipes accept productivity modules when `allow_productivity` says so, or otherwise when they
+/// belong to the `intermediate-products` group. Recipes flagged with `allow_decomposition` (or
+/// `reverse_recipe`) are marked as [`Recipe::reversible`], see [`DataSet::with_reverse_recipes`]
+/// for their inverses. An optional `version` string tags
+/// recipes of a single game version, see [`DataSet::filtered_by_version`].
+/// Products returned only with some `probability` are stored with their expected amount, so every
+/// calculation costs them by what a craft yields on average.
 pub struct DataSet {
     pub recipes: Vec<Recipe>,
     pub items: Vec<Item>,
+    demoted_naturals: Vec<String>,
+}
+
+impl RecipeJson {
+    fn into_recipe(self, natural_item_names: &[String]) -> FactoryResult<Recipe> {
+        // Recipes without a positive crafting time would make every per-second rate infinite or
+        // negative, so they are rejected instead of being silently accepted.
+        if self.time <= 0.0 {
+            return Err(FactoryError::NonPositiveRecipeTime(self.name));
+        }
+
+        let products = self.products.into_items();
+
+        let catalysts = products
+            .iter()
+            .filter_map(|prod| Some((prod.catalyst_amount.as_ref()?, prod)))
+            .map(|(amount, prod)| {
+                Ok((
+                    amount.to_decimal()?,
+                    Item {
+                        natural: natural_item_names.contains(&prod.name),
+                        name: prod.name.clone(),
+                    },
+                ))
+            })
+            .collect::<FactoryResult<Vec<(Decimal, Item)>>>()?
+            .into_iter()
+            .filter(|(amount, _)| *amount > Decimal::ZERO)
+            .collect();
+
+        let results: FactoryResult<Vec<(Decimal, Item)>> = products
+            .into_iter()
+            .map(|prod| {
+                Ok((
+                    prod.expected_amount()?,
+                    Item {
+                        natural: natural_item_names.contains(&prod.name),
+                        name: prod.name,
+                    },
+                ))
+            })
+            .collect();
+
+        let ingredients: FactoryResult<Vec<(Decimal, Item)>> = match self.ingredients {
+            IngredientField::Regular(items) => items
+                .into_iter()
+                .map(|item| {
+                    Ok((
+                        item.amount.to_decimal()?,
+                        Item {
+                            natural: natural_item_names.contains(&item.name),
+                            name: item.name,
+                        },
+                    ))
+                })
+                .collect(),
+            IngredientField::Empty {} => Ok(vec![]),
+        };
+
+        // Dumps don't carry the productivity module whitelist, but in vanilla it's made of
+        // the intermediate products group, so that's assumed unless stated explicitly.
+        let allows_productivity = self.allow_productivity.unwrap_or_else(|| {
+            self.group
+                .is_some_and(|group| group.name == "intermediate-products")
+        });
+
+        Ok(Recipe {
+            name: self.name,
+            results: results?,
+            catalysts,
+            ingredients: ingredients?,
+            time: Duration::from_secs_f64(self.time),
+            factory_kind: DataSet::category_into_factory_kind(&self.category),
+            category: self.category,
+            allows_productivity,
+            reversible: self.allow_decomposition,
+            version: self.version,
+        })
+    }
+}
+
+/// Recipe skipped by [`DataSet::from_str_lenient`].
+#[derive(Debug)]
+pub struct ParseError {
+    /// Key of the recipe in the dump.
+    pub key: String,
+    pub error: FactoryError,
+}
+
+/// Tweaks applied to the recipes while a [`DataSet`] is parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Net items which a recipe both consumes and produces, like the empty barrel returned by
+    /// emptying recipes, so only the net difference stays on the consuming or producing side.
+    /// Off by default, which keeps the recipes exactly as dumped.
+    pub net_barrel_returns: bool,
+    /// Keep items listed as natural even when some recipe produces them, like `water` next to a
+    /// pumping recipe, so they stay free roots of the graph at tier 0. Off by default, which
+    /// treats them as crafted from whatever the recipe consumes. Either way the items are listed,
+    /// by [`DataSet::produced_naturals`] when kept and by [`DataSet::demoted_naturals`] otherwise.
+    pub keep_produced_naturals: bool,
+    /// Smelt in stone furnaces and mine with burner mining drills
+    /// ([`FactoryKind::is_burner`]) instead of their electric counterparts, like early in the game.
+    /// Dumps don't say which building a recipe is crafted in, so this is up to the caller. Off by
+    /// default.
+    pub burner_buildings: bool,
 }
 
 impl traits::DataSource for DataSet {
@@ -42,54 +302,120 @@ impl traits::DataSource for DataSet {
     where
         Self: Sized,
     {
+        Self::from_str_with_options(recipes_str, natural_item_names, ParseOptions::default())
+    }
+
+    fn iter_items(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter()
+    }
+
+    fn iter_recipes(&self) -> impl Iterator<Item = &Recipe> {
+        self.recipes.iter()
+    }
+}
+
+impl DataSet {
+    /// Same as [`traits::DataSource::from_str`], but lets the caller tweak how recipes are parsed.
+    pub fn from_str_with_options(
+        recipes_str: &str,
+        natural_item_names: &[String],
+        options: ParseOptions,
+    ) -> FactoryResult<Self> {
         let recipes: HashMap<String, RecipeJson> =
             serde_json::from_str(recipes_str).map_err(FactoryError::JsonMalformed)?;
-        let recipes: Vec<Recipe> = recipes
-            .into_values()
-            .map(|rec| {
-                let results: FactoryResult<Vec<(Decimal, Item)>> = rec
-                    .products
-                    .into_iter()
-                    .map(|prod| {
-                        Ok((
-                            Decimal::from_usize(prod.amount).ok_or_else(|| {
-                                FactoryError::CantRepresentAmountAsDecimal(prod.amount)
-                            })?,
-                            Item {
-                                natural: natural_item_names.contains(&prod.name),
-                                name: prod.name,
-                            },
-                        ))
-                    })
-                    .collect();
-
-                let ingredients: FactoryResult<Vec<(Decimal, Item)>> = match rec.ingredients {
-                    IngredientField::Regular(items) => items
-                        .into_iter()
-                        .map(|item| {
-                            Ok((
-                                Decimal::from_usize(item.amount).ok_or_else(|| {
-                                    FactoryError::CantRepresentAmountAsDecimal(item.amount)
-                                })?,
-                                Item {
-                                    natural: natural_item_names.contains(&item.name),
-                                    name: item.name,
-                                },
-                            ))
-                        })
-                        .collect(),
-                    IngredientField::Empty {} => Ok(vec![]),
-                };
-
-                Ok(Recipe {
-                    name: rec.name,
-                    results: results?,
-                    ingredients: ingredients?,
-                    time: Duration::from_secs_f64(rec.time),
-                    factory_kind: Self::category_into_factory_kind(&rec.category),
+
+        Self::from_recipe_jsons(recipes.into_values(), natural_item_names, options)
+    }
+
+    /// Same as [`traits::DataSource::from_str`], but recipes which fail to parse are skipped instead
+    /// of failing the whole dump. Every skipped recipe is reported, ordered by its key.
+    /// The whole dump only fails when it isn't a JSON object at all, with
+    /// [`FactoryError::JsonMalformed`], or when the recipes kept disagree about whether an item is
+    /// natural, with [`FactoryError::InconsistentNaturalFlag`] (see [`DataSet::check_natural_flags`]).
+    pub fn from_str_lenient(
+        recipes_str: &str,
+        natural_item_names: &[String],
+    ) -> FactoryResult<(Self, Vec<ParseError>)> {
+        let entries: HashMap<String, serde_json::Value> =
+            serde_json::from_str(recipes_str).map_err(FactoryError::JsonMalformed)?;
+
+        let mut recipes = vec![];
+        let mut errors = vec![];
+        for (key, value) in entries
+            .into_iter()
+            .sorted_by(|(key1, _), (key2, _)| key1.cmp(key2))
+        {
+            let recipe = serde_json::from_value::<RecipeJson>(value)
+                .map_err(FactoryError::JsonMalformed)
+                .and_then(|rec| rec.into_recipe(natural_item_names));
+
+            match recipe {
+                Ok(recipe) => recipes.push(recipe),
+                Err(error) => errors.push(ParseError { key, error }),
+            }
+        }
+
+        let data = Self::from_recipes(recipes, ParseOptions::default());
+        data.check_natural_flags()?;
+
+        Ok((data, errors))
+    }
+
+    /// Load a data set from a directory holding one recipe per `*.json` file, each being a single
+    /// recipe object as found in recipe dumps. Other files are skipped.
+    /// Returns [`FactoryError::RecipeFileMalformed`] naming the file that failed to parse.
+    pub fn from_dir(dir: impl AsRef<Path>, natural_item_names: &[String]) -> FactoryResult<Self> {
+        let paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+
+        let recipes = paths
+            .into_iter()
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+            .sorted()
+            .map(|path| {
+                let file_content = fs::read_to_string(&path)?;
+
+                serde_json::from_str::<RecipeJson>(&file_content).map_err(|error| {
+                    FactoryError::RecipeFileMalformed {
+                        file: path.display().to_string(),
+                        error,
+                    }
                 })
             })
+            .collect::<FactoryResult<Vec<RecipeJson>>>()?;
+
+        Self::from_recipe_jsons(recipes, natural_item_names, ParseOptions::default())
+    }
+
+    fn from_recipe_jsons(
+        recipes: impl IntoIterator<Item = RecipeJson>,
+        natural_item_names: &[String],
+        options: ParseOptions,
+    ) -> FactoryResult<Self> {
+        let recipes: Vec<Recipe> = recipes
+            .into_iter()
+            .map(|rec| rec.into_recipe(natural_item_names))
             .collect::<FactoryResult<Vec<Recipe>>>()?;
+
+        let data = Self::from_recipes(recipes, options);
+        data.check_natural_flags()?;
+
+        Ok(data)
+    }
+
+    fn from_recipes(recipes: Vec<Recipe>, options: ParseOptions) -> Self {
+        let (recipes, demoted_naturals) = demote_produced_naturals(
+            normalise_recipes(recipes, options),
+            options.keep_produced_naturals,
+        );
+
+        Self::from_normalised_recipes(recipes, demoted_naturals)
+    }
+
+    /// Build a data set from recipes which went through [`normalise_recipes`] and natural demotion
+    /// already, collecting the items they mention.
+    fn from_normalised_recipes(recipes: Vec<Recipe>, demoted_naturals: Vec<String>) -> Self {
         let items = recipes
             .iter()
             .flat_map(|recipe| recipe.ingredients.iter())
@@ -99,19 +425,69 @@ impl traits::DataSource for DataSet {
             .cloned()
             .collect();
 
-        Ok(Self { recipes, items })
+        Self {
+            recipes,
+            items,
+            demoted_naturals,
+        }
     }
 
-    fn iter_items(&self) -> impl Iterator<Item = &Item> {
-        self.items.iter()
+    /// Get names of the items listed as natural but treated as crafted, because some recipe
+    /// produces them (see [`ParseOptions::keep_produced_naturals`]), ordered by name.
+    pub fn demoted_naturals(&self) -> &[String] {
+        &self.demoted_naturals
     }
 
-    fn iter_recipes(&self) -> impl Iterator<Item = &Recipe> {
-        self.recipes.iter()
+    /// Get natural items which some recipe produces too, ordered by name. These are only left when
+    /// parsed with [`ParseOptions::keep_produced_naturals`], otherwise they're crafted items.
+    pub fn produced_naturals(&self) -> Vec<&Item> {
+        let produced = produced_naturals(&self.recipes);
+
+        self.items
+            .iter()
+            .filter(|item| item.natural && produced.contains(&item.name))
+            .sorted_by(|item1, item2| item1.name.cmp(&item2.name))
+            .collect()
+    }
+
+    /// Check that every occurrence of an item, in the item list and in the recipes, agrees on
+    /// whether it's natural. The graph tells items apart by their whole value, so an item flagged
+    /// differently in two recipes would turn into two separate nodes.
+    /// Parsing flags items from a single list of natural names, so only recipes built by hand can
+    /// disagree: ones passed to [`DataSet::add_recipes`], which checks them as well, or ones pushed
+    /// to [`DataSet::recipes`] directly.
+    /// Returns [`FactoryError::InconsistentNaturalFlag`] naming the first such item by name.
+    pub fn check_natural_flags(&self) -> FactoryResult<()> {
+        let mut natural_by_name: HashMap<&str, HashSet<bool>> = HashMap::new();
+        let occurrences = self
+            .items
+            .iter()
+            .chain(self.recipes.iter().flat_map(|recipe| {
+                recipe
+                    .ingredients
+                    .iter()
+                    .chain(recipe.results.iter())
+                    .chain(recipe.catalysts.iter())
+                    .map(|(_, item)| item)
+            }));
+        for item in occurrences {
+            natural_by_name
+                .entry(&item.name)
+                .or_default()
+                .insert(item.natural);
+        }
+
+        match natural_by_name
+            .into_iter()
+            .filter(|(_, flags)| flags.len() > 1)
+            .map(|(name, _)| name)
+            .min()
+        {
+            Some(name) => Err(FactoryError::InconsistentNaturalFlag(name.to_string())),
+            None => Ok(()),
+        }
     }
-}
 
-impl DataSet {
     pub fn natural_items(&self) -> Vec<&Item> {
         self.items.iter().filter(|item| item.natural).collect()
     }
@@ -131,6 +507,51 @@ impl DataSet {
         self.recipes.iter().find(|recipe| recipe.name == name)
     }
 
+    /// Get every item needed, directly or through the recipes producing its ingredients, to craft
+    /// the recipe, down to the natural items. All producers of an ingredient are followed, and
+    /// every item is expanded only once, so crafting cycles end. Items are ordered by name.
+    /// Unknown recipes need nothing.
+    pub fn transitive_ingredients(&self, recipe_name: &str) -> Vec<&Item> {
+        let Some(recipe) = self.try_get_recipe(recipe_name) else {
+            return vec![];
+        };
+
+        let mut needed: HashMap<&str, &Item> = HashMap::new();
+        let mut pending = recipe
+            .ingredients
+            .iter()
+            .map(|(_, item)| item)
+            .collect_vec();
+        while let Some(item) = pending.pop() {
+            if needed.insert(&item.name, item).is_some() || item.natural {
+                continue;
+            }
+
+            pending.extend(
+                self.recipes
+                    .iter()
+                    .filter(|producer| {
+                        producer
+                            .results
+                            .iter()
+                            .any(|(_, result)| result.name == item.name)
+                    })
+                    .flat_map(|producer| producer.ingredients.iter().map(|(_, item)| item)),
+            );
+        }
+
+        needed
+            .into_values()
+            .sorted_by(|item1, item2| item1.name.cmp(&item2.name))
+            .collect()
+    }
+
+    /// Get the building crafting the recipe, or None for an unknown recipe.
+    pub fn factory_kind_for(&self, recipe_name: &str) -> Option<FactoryKind> {
+        self.try_get_recipe(recipe_name)
+            .map(|recipe| recipe.factory_kind.clone())
+    }
+
     pub fn get_recipe(&self, name: &str) -> &Recipe {
         self.recipes
             .iter()
@@ -147,4 +568,1522 @@ impl DataSet {
 
         self
     }
+
+    /// Add the inverse of every [`Recipe::reversible`] recipe ([`Recipe::reversed`]) which isn't
+    /// there yet, for graphs built with [`crate::domain::GraphOptions::reverse_recipes`]. The
+    /// inverses only use items of the recipes they reverse, so the items stay as they are.
+    pub fn with_reverse_recipes(mut self) -> Self {
+        let reversed = self
+            .recipes
+            .iter()
+            .filter_map(Recipe::reversed)
+            .filter(|reversed| self.try_get_recipe(&reversed.name).is_none())
+            .collect_vec();
+        self.recipes.extend(reversed);
+
+        self
+    }
+
+    /// Tag every recipe which doesn't name its version yet with `version`, e.g. right after loading
+    /// a dump of a single game version, so it can be merged with dumps of other versions.
+    pub fn with_version(mut self, version: &str) -> Self {
+        for recipe in &mut self.recipes {
+            recipe.version.get_or_insert_with(|| version.to_string());
+        }
+
+        self
+    }
+
+    /// Get a data set with only the recipes of the given game version and the version-agnostic
+    /// ones (without [`Recipe::version`]), together with the items they use.
+    pub fn filtered_by_version(&self, version: &str) -> Self {
+        let recipes = self
+            .recipes
+            .iter()
+            .filter(|recipe| recipe.version.as_ref().is_none_or(|v| v == version))
+            .cloned()
+            .collect();
+
+        // The recipes were normalised already, their natural flags are kept as they are.
+        let mut filtered = Self::from_normalised_recipes(recipes, vec![]);
+        filtered.demoted_naturals = self
+            .demoted_naturals
+            .iter()
+            .filter(|name| filtered.try_get_item(name).is_some())
+            .cloned()
+            .collect();
+
+        filtered
+    }
+
+    /// Get a fingerprint of the recipes, for telling whether cached results computed from a data set
+    /// are still valid. It covers every field of every recipe, including the natural flags of its
+    /// items, but not the order of recipes or of their ingredients, results and catalysts, so data
+    /// sets loaded from differently ordered dumps share it. Every field is length-prefixed, so
+    /// names holding separators can't make two different data sets collide.
+    /// The hash is FNV-1a, which unlike [`std::collections::hash_map::DefaultHasher`] gives the same
+    /// value across runs, platforms and Rust versions.
+    pub fn fingerprint(&self) -> u64 {
+        let flow = |flow: &[(Decimal, Item)]| {
+            let entries = flow
+                .iter()
+                .map(|(amount, item)| {
+                    fingerprint_field(&amount.normalize().to_string())
+                        + &fingerprint_field(&item.name)
+                        + &fingerprint_field(&item.natural.to_string())
+                })
+                .sorted()
+                .join("");
+            fingerprint_field(&entries)
+        };
+        let canonical = self
+            .recipes
+            .iter()
+            .map(|recipe| {
+                let fields = [
+                    fingerprint_field(&recipe.name),
+                    fingerprint_field(&recipe.category),
+                    fingerprint_field(&format!("{:?}", recipe.factory_kind)),
+                    fingerprint_field(&recipe.time.as_nanos().to_string()),
+                    flow(&recipe.ingredients),
+                    flow(&recipe.results),
+                    flow(&recipe.catalysts),
+                    fingerprint_field(&recipe.allows_productivity.to_string()),
+                    fingerprint_field(&recipe.reversible.to_string()),
+                    recipe
+                        .version
+                        .as_deref()
+                        .map_or("-".to_string(), fingerprint_field),
+                ];
+                fingerprint_field(&fields.join(""))
+            })
+            .sorted()
+            .join("");
+
+        fnv1a(canonical.as_bytes())
+    }
+
+    /// List every item and recipe, ordered by name, one per line:
+    ///
+    /// ```text
+    /// items:
+    ///   iron-ore (natural)
+    ///   iron-plate
+    /// recipes:
+    ///   iron-plate [Smelter]: 1 iron-ore -> 1 iron-plate
+    /// ```
+    pub fn catalog(&self) -> String {
+        let flow = |flow: &[(Decimal, Item)]| {
+            flow.iter()
+                .map(|(amount, item)| format!("{} {}", amount.normalize(), item.name))
+                .join(" + ")
+        };
+
+        let items = self
+            .items
+            .iter()
+            .sorted_by(|item1, item2| item1.name.cmp(&item2.name))
+            .map(|item| {
+                if item.natural {
+                    format!("  {} (natural)", item.name)
+                } else {
+                    format!("  {}", item.name)
+                }
+            });
+        let recipes = self
+            .recipes
+            .iter()
+            .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+            .map(|recipe| {
+                format!(
+                    "  {} [{:?}]: {} -> {}",
+                    recipe.name,
+                    recipe.factory_kind,
+                    flow(&recipe.ingredients),
+                    flow(&recipe.results)
+                )
+            });
+
+        ["items:".to_string()]
+            .into_iter()
+            .chain(items)
+            .chain(["recipes:".to_string()])
+            .chain(recipes)
+            .join("\n")
+    }
+
+    /// Render the recipes as a bipartite item/recipe graph in DOT, straight from `recipes` and
+    /// `items`, without building a [`crate::domain::CraftingGraph`] or computing tiers. This tells
+    /// parsing problems apart from graph construction ones. Items are ellipses, recipes boxes,
+    /// edges are labeled with the amounts, everything ordered by name. Nodes use the IDs of
+    /// [`crate::domain::CraftingGraph::node_id`]:
+    ///
+    /// ```text
+    /// digraph {
+    ///     "item:iron-ore" [label = "iron-ore", shape = ellipse]
+    ///     "recipe:iron-plate" [label = "iron-plate", shape = box]
+    ///     "item:iron-ore" -> "recipe:iron-plate" [label = "1"]
+    /// }
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let item_id = |item: &Item| format!("{:?}", format!("item:{}", item.name));
+        let recipe_id = |recipe: &Recipe| format!("{:?}", format!("recipe:{}", recipe.name));
+
+        let items = self
+            .items
+            .iter()
+            .sorted_by(|item1, item2| item1.name.cmp(&item2.name))
+            .map(|item| {
+                format!(
+                    "    {} [label = {:?}, shape = ellipse]",
+                    item_id(item),
+                    item.name
+                )
+            });
+        let recipes = self
+            .recipes
+            .iter()
+            .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+            .collect_vec();
+        let recipe_nodes = recipes.iter().map(|recipe| {
+            format!(
+                "    {} [label = {:?}, shape = box]",
+                recipe_id(recipe),
+                recipe.name
+            )
+        });
+        let edges = recipes.iter().flat_map(|recipe| {
+            let ingredients = recipe.ingredients.iter().map(|(amount, item)| {
+                format!(
+                    "    {} -> {} [label = \"{}\"]",
+                    item_id(item),
+                    recipe_id(recipe),
+                    amount.normalize()
+                )
+            });
+            let results = recipe.results.iter().map(|(amount, item)| {
+                format!(
+                    "    {} -> {} [label = \"{}\"]",
+                    recipe_id(recipe),
+                    item_id(item),
+                    amount.normalize()
+                )
+            });
+
+            ingredients.chain(results).collect_vec()
+        });
+
+        ["digraph {".to_string()]
+            .into_iter()
+            .chain(items)
+            .chain(recipe_nodes)
+            .chain(edges)
+            .chain(["}".to_string()])
+            .join("\n")
+    }
+
+    /// Append a recipe to the data set, see [`DataSet::add_recipes`].
+    pub fn add_recipe(&mut self, recipe: Recipe) -> FactoryResult<()> {
+        self.add_recipes(vec![recipe])
+    }
+
+    /// Append recipes to the data set, registering the items they introduce. Same as
+    /// [`DataSet::add_recipes_with_options`] with the default [`ParseOptions`].
+    pub fn add_recipes(&mut self, recipes: Vec<Recipe>) -> FactoryResult<()> {
+        self.add_recipes_with_options(recipes, ParseOptions::default())
+    }
+
+    /// Append recipes to the data set, checked and normalised the way parsing does: recipes without
+    /// crafting time fail with [`FactoryError::NonPositiveRecipeTime`], and `options` apply to the
+    /// added recipes only, the ones already in the data set keep their normalisation and natural
+    /// flags. Natural items which only the added recipes produce are demoted, unless `options`
+    /// keep them, and the items are recomputed from the recipes. Fails with
+    /// [`FactoryError::InconsistentNaturalFlag`] when the added recipes disagree with the data set
+    /// about whether an item is natural.
+    /// The data set is left untouched on failure.
+    pub fn add_recipes_with_options(
+        &mut self,
+        recipes: Vec<Recipe>,
+        options: ParseOptions,
+    ) -> FactoryResult<()> {
+        if let Some(recipe) = recipes.iter().find(|recipe| recipe.time.is_zero()) {
+            return Err(FactoryError::NonPositiveRecipeTime(recipe.name.clone()));
+        }
+
+        let produced_before = produced_naturals(&self.recipes);
+        let mut recipes = self
+            .recipes
+            .iter()
+            .cloned()
+            .chain(normalise_recipes(recipes, options))
+            .collect_vec();
+
+        let newly_demoted = if options.keep_produced_naturals {
+            HashSet::new()
+        } else {
+            produced_naturals(&recipes)
+                .into_iter()
+                .filter(|name| !produced_before.contains(name))
+                .collect()
+        };
+        demote_naturals(&mut recipes, &newly_demoted);
+
+        let demoted_naturals = self
+            .demoted_naturals
+            .iter()
+            .cloned()
+            .chain(newly_demoted)
+            .sorted()
+            .dedup()
+            .collect();
+        let data = Self::from_normalised_recipes(recipes, demoted_naturals);
+        data.check_natural_flags()?;
+        *self = data;
+
+        Ok(())
+    }
+
+    /// Get recipes none of whose results leads anywhere: no other recipe consumes them, and no
+    /// recipe is named after them, which is how final products like science packs look. These
+    /// usually come from a misspelled item name. Recipes are ordered by name.
+    pub fn recipes_with_dangling_outputs(&self) -> Vec<&Recipe> {
+        let leads_somewhere = |recipe: &Recipe, item: &Item| {
+            self.recipes.iter().any(|other| {
+                other.name == item.name
+                    || (other != recipe
+                        && other
+                            .ingredients
+                            .iter()
+                            .any(|(_, ingredient)| ingredient.name == item.name))
+            })
+        };
+
+        self.recipes
+            .iter()
+            .filter(|recipe| !recipe.results.is_empty())
+            .filter(|recipe| {
+                !recipe
+                    .results
+                    .iter()
+                    .any(|(_, item)| leads_somewhere(recipe, item))
+            })
+            .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+            .collect()
+    }
+
+    /// Group recipes which consume exactly the same ingredients in the same amounts (in any order)
+    /// but produce different results, like competing oil processing variants.
+    /// Only groups with at least two recipes are returned. Recipes inside a group, as well as the
+    /// groups themselves, are ordered by recipe name.
+    pub fn recipes_with_shared_inputs(&self) -> Vec<Vec<&Recipe>> {
+        self.recipes
+            .iter()
+            .filter(|recipe| !recipe.ingredients.is_empty())
+            .into_group_map_by(|recipe| sorted_flow(&recipe.ingredients))
+            .into_values()
+            .filter(|group| {
+                group
+                    .iter()
+                    .map(|recipe| sorted_flow(&recipe.results))
+                    .unique()
+                    .count()
+                    > 1
+            })
+            .map(|group| {
+                group
+                    .into_iter()
+                    .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+                    .collect_vec()
+            })
+            .sorted_by(|group1, group2| group1[0].name.cmp(&group2[0].name))
+            .collect()
+    }
+}
+
+/// Move a smelting or mining recipe to the burner building of the same purpose.
+fn use_burner_building(mut recipe: Recipe) -> Recipe {
+    recipe.factory_kind = match recipe.factory_kind {
+        FactoryKind::Smelter => FactoryKind::BurnerSmelter,
+        FactoryKind::MiningDrill => FactoryKind::BurnerMiningDrill,
+        other => other,
+    };
+
+    recipe
+}
+
+/// Encode a field of [`DataSet::fingerprint`] as its byte length, a colon and the field itself.
+fn fingerprint_field(field: &str) -> String {
+    format!("{}:{field}", field.len())
+}
+
+/// 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Names of items listed as natural but produced by some recipe, like `water` in the presence of
+/// a pumping recipe. Mining recipes are what natural items come from, and recipes only giving back
+/// what was made from the item (like emptying a barrel) don't create any new supply, so neither
+/// counts.
+fn produced_naturals(recipes: &[Recipe]) -> HashSet<String> {
+    recipes
+        .iter()
+        .filter(|recipe| !recipe.factory_kind.is_mining())
+        .flat_map(|recipe| {
+            recipe
+                .results
+                .iter()
+                .filter(|(_, item)| item.natural)
+                .map(move |(_, item)| (recipe, item))
+        })
+        .filter(|(recipe, item)| {
+            let made_from_item = made_from(recipes, &item.name);
+            !recipe
+                .ingredients
+                .iter()
+                .any(|(_, ingredient)| made_from_item.contains(&ingredient.name))
+        })
+        .map(|(_, item)| item.name.clone())
+        .collect()
+}
+
+/// Apply the per-recipe tweaks of `options` to every recipe. Natural demotion is up to the caller,
+/// as it depends on the whole data set.
+fn normalise_recipes(recipes: Vec<Recipe>, options: ParseOptions) -> Vec<Recipe> {
+    let recipes = if options.net_barrel_returns {
+        recipes.into_iter().map(net_returned_items).collect()
+    } else {
+        recipes
+    };
+
+    if options.burner_buildings {
+        recipes.into_iter().map(use_burner_building).collect()
+    } else {
+        recipes
+    }
+}
+
+/// Treat [`produced_naturals`] as regular crafted items, unless `keep_natural` is set, in which
+/// case they stay free roots. Also returns names of the demoted items, ordered by name.
+fn demote_produced_naturals(
+    mut recipes: Vec<Recipe>,
+    keep_natural: bool,
+) -> (Vec<Recipe>, Vec<String>) {
+    if keep_natural {
+        return (recipes, vec![]);
+    }
+    let produced = produced_naturals(&recipes);
+    demote_naturals(&mut recipes, &produced);
+
+    (recipes, produced.into_iter().sorted().collect())
+}
+
+/// Mark every occurrence of the `names` items in the recipes as crafted.
+fn demote_naturals(recipes: &mut [Recipe], names: &HashSet<String>) {
+    for recipe in recipes {
+        for (_, item) in recipe
+            .ingredients
+            .iter_mut()
+            .chain(recipe.results.iter_mut())
+            .chain(recipe.catalysts.iter_mut())
+        {
+            if names.contains(&item.name) {
+                item.natural = false;
+            }
+        }
+    }
+}
+
+/// Names of the item and of everything crafted from it, directly or through other intermediates.
+fn made_from(recipes: &[Recipe], item_name: &str) -> HashSet<String> {
+    let mut made_from = HashSet::from([item_name.to_string()]);
+
+    loop {
+        let newly_made = recipes
+            .iter()
+            .filter(|recipe| {
+                recipe
+                    .ingredients
+                    .iter()
+                    .any(|(_, ingredient)| made_from.contains(&ingredient.name))
+            })
+            .flat_map(|recipe| recipe.results.iter())
+            .map(|(_, item)| item.name.clone())
+            .filter(|name| !made_from.contains(name))
+            .collect_vec();
+
+        if newly_made.is_empty() {
+            return made_from;
+        }
+        made_from.extend(newly_made);
+    }
+}
+
+/// Cancel out items which are both consumed and produced by the recipe, keeping only the net
+/// difference on one side. Catalyst amounts shrink along with the results they describe.
+fn net_returned_items(mut recipe: Recipe) -> Recipe {
+    for (ingredient_amount, ingredient) in &mut recipe.ingredients {
+        let Some((result_amount, _)) = recipe
+            .results
+            .iter_mut()
+            .find(|(_, result)| result.name == ingredient.name)
+        else {
+            continue;
+        };
+
+        let returned = (*ingredient_amount).min(*result_amount);
+        *ingredient_amount -= returned;
+        *result_amount -= returned;
+
+        if let Some((catalyst_amount, _)) = recipe
+            .catalysts
+            .iter_mut()
+            .find(|(_, catalyst)| catalyst.name == ingredient.name)
+        {
+            *catalyst_amount = (*catalyst_amount - returned).max(Decimal::ZERO);
+        }
+    }
+
+    recipe.ingredients.retain(|(amount, _)| !amount.is_zero());
+    recipe.results.retain(|(amount, _)| !amount.is_zero());
+    recipe.catalysts.retain(|(amount, _)| !amount.is_zero());
+
+    recipe
+}
+
+/// Order-insensitive representation of recipe ingredients or results.
+fn sorted_flow(flow: &[(Decimal, Item)]) -> Vec<(&str, Decimal)> {
+    flow.iter()
+        .map(|(amount, item)| (item.name.as_str(), amount.normalize()))
+        .sorted()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        entities::{FactoryKind, Item, Recipe},
+        error::FactoryError,
+        traits::DataSource as _,
+    };
+
+    use super::{DataSet, ParseOptions};
+
+    #[test]
+    fn test_net_results_subtract_catalyst() {
+        let recipes = r#"{
+            "kovarex-enrichment-process": {
+                "name": "kovarex-enrichment-process",
+                "category": "centrifuging",
+                "energy": 60,
+                "ingredients": [
+                    {"name": "uranium-235", "amount": 40, "catalyst_amount": 40},
+                    {"name": "uranium-238", "amount": 5, "catalyst_amount": 2}
+                ],
+                "products": [
+                    {"name": "uranium-235", "amount": 41, "catalyst_amount": 40},
+                    {"name": "uranium-238", "amount": 2, "catalyst_amount": 2}
+                ]
+            },
+            "uranium-processing": {
+                "name": "uranium-processing",
+                "category": "centrifuging",
+                "energy": 12,
+                "ingredients": [{"name": "uranium-ore", "amount": 10}],
+                "products": [{"name": "uranium-238", "amount": 1}]
+            }
+        }"#;
+
+        let data = DataSet::from_str(recipes, &["uranium-ore".to_string()]).unwrap();
+
+        let kovarex = data.get_recipe("kovarex-enrichment-process");
+        let net_results = kovarex
+            .net_results()
+            .into_iter()
+            .map(|(amount, item)| (item.name.as_str(), amount))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            net_results,
+            vec![("uranium-235", dec!(1)), ("uranium-238", dec!(0))]
+        );
+
+        let processing = data.get_recipe("uranium-processing");
+        let net_results = processing
+            .net_results()
+            .into_iter()
+            .map(|(amount, item)| (item.name.as_str(), amount))
+            .collect::<Vec<_>>();
+        assert_eq!(net_results, vec![("uranium-238", dec!(1))]);
+    }
+
+    #[test]
+    fn test_product_field_variants() {
+        let recipes = r#"{
+            "iron-gear-wheel": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}]
+            },
+            "copper-cable": {
+                "name": "copper-cable",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "copper-plate", "amount": 1}],
+                "results": [{"name": "copper-cable", "amount": 2}]
+            },
+            "pipe": {
+                "name": "pipe",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 1}],
+                "result": "pipe"
+            },
+            "iron-stick": {
+                "name": "iron-stick",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 1}],
+                "result": "iron-stick",
+                "result_count": 2
+            }
+        }"#;
+
+        let data = DataSet::from_str(recipes, &[]).unwrap();
+
+        for (recipe, amount) in [
+            ("iron-gear-wheel", dec!(1)),
+            ("copper-cable", dec!(2)),
+            ("pipe", dec!(1)),
+            ("iron-stick", dec!(2)),
+        ] {
+            let results = &data.get_recipe(recipe).results;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].0, amount);
+            assert_eq!(results[0].1.name, recipe);
+        }
+    }
+
+    #[test]
+    fn test_recipes_with_shared_inputs() {
+        let recipes = r#"{
+            "basic-oil-processing": {
+                "name": "basic-oil-processing",
+                "category": "oil-processing",
+                "energy": 5,
+                "ingredients": [{"name": "crude-oil", "amount": 100}],
+                "products": [{"name": "petroleum-gas", "amount": 45}]
+            },
+            "heavy-oil-processing": {
+                "name": "heavy-oil-processing",
+                "category": "oil-processing",
+                "energy": 5,
+                "ingredients": [{"name": "crude-oil", "amount": 100}],
+                "products": [{"name": "heavy-oil", "amount": 30}]
+            },
+            "advanced-oil-processing": {
+                "name": "advanced-oil-processing",
+                "category": "oil-processing",
+                "energy": 5,
+                "ingredients": [
+                    {"name": "water", "amount": 50},
+                    {"name": "crude-oil", "amount": 100}
+                ],
+                "products": [{"name": "heavy-oil", "amount": 25}]
+            }
+        }"#;
+
+        let data = DataSet::from_str(recipes, &["crude-oil".to_string()]).unwrap();
+
+        let groups = data
+            .recipes_with_shared_inputs()
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(|recipe| recipe.name.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            groups,
+            vec![vec!["basic-oil-processing", "heavy-oil-processing"]]
+        );
+    }
+
+    #[test]
+    fn test_with_reverse_recipes() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "iron-gear-wheel": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}],
+                "allow_decomposition": true
+            }
+        }"#;
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()])
+            .unwrap()
+            .with_reverse_recipes()
+            .with_reverse_recipes();
+
+        assert_eq!(data.recipes.len(), 3);
+        assert_eq!(data.items.len(), 3);
+        let reversed = data.get_recipe("iron-gear-wheel~reverse");
+        assert!(reversed.is_reverse());
+        assert!(!reversed.reversible);
+        assert_eq!(
+            reversed.ingredients,
+            data.get_recipe("iron-gear-wheel").results
+        );
+        assert_eq!(
+            reversed.results,
+            data.get_recipe("iron-gear-wheel").ingredients
+        );
+        assert!(data.get_recipe("iron-plate").reversed().is_none());
+    }
+
+    #[test]
+    fn test_filtered_by_version() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "iron-gear-wheel-1.0": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}],
+                "version": "1.0"
+            },
+            "iron-gear-wheel-2.0": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-ore", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}],
+                "version": "2.0"
+            }
+        }"#;
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+
+        let old = data.filtered_by_version("1.0");
+        assert_eq!(old.recipes.len(), 2);
+        assert_eq!(
+            old.get_recipe("iron-gear-wheel").ingredients[0].1.name,
+            "iron-plate"
+        );
+        assert_eq!(old.items.len(), 3);
+
+        let new = data.filtered_by_version("2.0");
+        assert_eq!(
+            new.get_recipe("iron-gear-wheel").ingredients[0].1.name,
+            "iron-ore"
+        );
+        assert_eq!(data.filtered_by_version("3.0").recipes.len(), 1);
+
+        let tagged = data.with_version("1.5");
+        assert_eq!(
+            tagged.get_recipe("iron-plate").version.as_deref(),
+            Some("1.5")
+        );
+        assert_eq!(tagged.filtered_by_version("2.0").recipes.len(), 1);
+    }
+
+    #[test]
+    fn test_check_natural_flags() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "iron-stick": {
+                "name": "iron-stick",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-stick", "amount": 2}]
+            }
+        }"#;
+        let mut data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+        assert!(data.check_natural_flags().is_ok());
+
+        let stick = data
+            .recipes
+            .iter_mut()
+            .find(|recipe| recipe.name == "iron-stick")
+            .unwrap();
+        stick.ingredients[0].1.natural = false;
+
+        assert!(matches!(
+            data.check_natural_flags(),
+            Err(FactoryError::InconsistentNaturalFlag(name)) if name == "iron-ore"
+        ));
+    }
+
+    #[test]
+    fn test_transitive_ingredients() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "iron-gear-wheel": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}]
+            },
+            "gear-recycling": {
+                "name": "gear-recycling",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-gear-wheel", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "inserter": {
+                "name": "inserter",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [
+                    {"name": "iron-gear-wheel", "amount": 1},
+                    {"name": "iron-plate", "amount": 1}
+                ],
+                "products": [{"name": "inserter", "amount": 1}]
+            }
+        }"#;
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+        let names = |recipe_name: &str| {
+            data.transitive_ingredients(recipe_name)
+                .into_iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names("inserter"),
+            vec!["iron-gear-wheel", "iron-ore", "iron-plate"]
+        );
+        assert_eq!(names("iron-plate"), vec!["iron-ore"]);
+        assert!(names("nonexistent").is_empty());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "inserter": {
+                "name": "inserter",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [
+                    {"name": "iron-gear-wheel", "amount": 1},
+                    {"name": "iron-plate", "amount": 1}
+                ],
+                "products": [{"name": "inserter", "amount": 1}]
+            }
+        }"#;
+        let naturals = ["iron-ore".to_string()];
+        let data = DataSet::from_str(recipes, &naturals).unwrap();
+
+        let mut reordered = DataSet::from_str(recipes, &naturals).unwrap();
+        reordered.recipes.reverse();
+        for recipe in &mut reordered.recipes {
+            recipe.ingredients.reverse();
+        }
+        assert_eq!(reordered.fingerprint(), data.fingerprint());
+
+        let slower = DataSet::from_str(&recipes.replace("3.2", "6.4"), &naturals).unwrap();
+        assert_ne!(slower.fingerprint(), data.fingerprint());
+
+        let mut changed = DataSet::from_str(recipes, &naturals).unwrap();
+        changed.recipes[0].allows_productivity = !changed.recipes[0].allows_productivity;
+        assert_ne!(changed.fingerprint(), data.fingerprint());
+        let changed = DataSet::from_str(recipes, &[]).unwrap();
+        assert_ne!(changed.fingerprint(), data.fingerprint());
+        let changed = DataSet::from_str(recipes, &naturals)
+            .unwrap()
+            .with_version("2.0");
+        assert_ne!(changed.fingerprint(), data.fingerprint());
+        let mut changed = DataSet::from_str(recipes, &naturals).unwrap();
+        let (_, plate) = changed.recipes[0].results[0].clone();
+        changed.recipes[0].catalysts.push((dec!(1), plate));
+        assert_ne!(changed.fingerprint(), data.fingerprint());
+
+        // Separators inside names don't shift fields into each other.
+        let split = |name: &str, category: &str| {
+            let mut data = DataSet::from_str(recipes, &naturals).unwrap();
+            data.recipes[0].name = name.to_string();
+            data.recipes[0].category = category.to_string();
+            data.fingerprint()
+        };
+        assert_ne!(split("a|b", "c"), split("a", "b|c"));
+        assert_ne!(split("a,b", "c"), split("a", "b,c"));
+
+        assert_eq!(super::fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(super::fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_add_recipe() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            }
+        }"#;
+        let mut data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+        assert_eq!(data.items.len(), 2);
+
+        let iron_plate = data.get_item("iron-plate").clone();
+        data.add_recipe(Recipe {
+            name: "iron-gear-wheel".to_string(),
+            results: vec![(
+                dec!(1),
+                Item {
+                    name: "iron-gear-wheel".to_string(),
+                    natural: false,
+                },
+            )],
+            catalysts: vec![],
+            ingredients: vec![(dec!(2), iron_plate.clone())],
+            time: Duration::from_secs_f64(0.5),
+            category: "crafting".to_string(),
+            factory_kind: FactoryKind::Assembler,
+            allows_productivity: true,
+            reversible: false,
+            version: None,
+        })
+        .unwrap();
+
+        assert_eq!(data.recipes.len(), 2);
+        assert_eq!(data.items.len(), 3);
+        assert!(data.try_get_item("iron-gear-wheel").is_some());
+        assert_eq!(data.natural_items(), vec![data.get_item("iron-ore")]);
+
+        let iron_ore = data.get_item("iron-ore").clone();
+        let ore_recipe = |name: &str, time: f64, ore: Item| Recipe {
+            name: name.to_string(),
+            results: vec![(dec!(1), ore)],
+            catalysts: vec![],
+            ingredients: vec![(dec!(1), iron_plate.clone())],
+            time: Duration::from_secs_f64(time),
+            category: "crafting".to_string(),
+            factory_kind: FactoryKind::Assembler,
+            allows_productivity: false,
+            reversible: false,
+            version: None,
+        };
+
+        let result = data.add_recipe(ore_recipe("instant-ore", 0.0, iron_ore.clone()));
+        assert!(
+            matches!(result, Err(FactoryError::NonPositiveRecipeTime(ref name)) if name == "instant-ore")
+        );
+        let crafted_ore = Item {
+            natural: false,
+            ..iron_ore.clone()
+        };
+        let result = data.add_recipe(ore_recipe("unsmelting", 1.0, crafted_ore));
+        assert!(
+            matches!(result, Err(FactoryError::InconsistentNaturalFlag(ref name)) if name == "iron-ore")
+        );
+        assert_eq!(data.recipes.len(), 2);
+
+        // Iron ore is now produced from something else than what's made of it, so it's crafted.
+        let wood = Item {
+            name: "wood".to_string(),
+            natural: true,
+        };
+        data.add_recipe(Recipe {
+            ingredients: vec![(dec!(1), wood)],
+            ..ore_recipe("petrified-ore", 1.0, iron_ore)
+        })
+        .unwrap();
+        assert_eq!(data.recipes.len(), 3);
+        assert!(!data.get_item("iron-ore").natural);
+        assert_eq!(data.natural_items(), vec![data.get_item("wood")]);
+        assert!(data.check_natural_flags().is_ok());
+    }
+
+    #[test]
+    fn test_non_positive_recipe_time_is_rejected() {
+        for energy in ["0", "-1.5"] {
+            let recipes = format!(
+                r#"{{
+                    "instant-plate": {{
+                        "name": "instant-plate",
+                        "category": "smelting",
+                        "energy": {energy},
+                        "ingredients": [{{"name": "iron-ore", "amount": 1}}],
+                        "products": [{{"name": "iron-plate", "amount": 1}}]
+                    }}
+                }}"#
+            );
+
+            let result = DataSet::from_str(&recipes, &["iron-ore".to_string()]);
+
+            assert!(
+                matches!(result, Err(FactoryError::NonPositiveRecipeTime(ref name)) if name == "instant-plate")
+            );
+        }
+    }
+
+    #[test]
+    fn test_net_barrel_returns() {
+        let recipes = r#"{
+            "coal-liquefaction": {
+                "name": "coal-liquefaction",
+                "category": "oil-processing",
+                "energy": 5,
+                "ingredients": [
+                    {"name": "coal", "amount": 10},
+                    {"name": "heavy-oil", "amount": 25, "catalyst_amount": 25},
+                    {"name": "steam", "amount": 50}
+                ],
+                "products": [
+                    {"name": "heavy-oil", "amount": 90, "catalyst_amount": 25},
+                    {"name": "light-oil", "amount": 20},
+                    {"name": "petroleum-gas", "amount": 10}
+                ]
+            }
+        }"#;
+        let naturals = ["coal".to_string(), "steam".to_string()];
+
+        let raw = DataSet::from_str(recipes, &naturals).unwrap();
+        let liquefaction = raw.get_recipe("coal-liquefaction");
+        assert_eq!(liquefaction.ingredients.len(), 3);
+        assert_eq!(liquefaction.catalysts.len(), 1);
+
+        let netted = DataSet::from_str_with_options(
+            recipes,
+            &naturals,
+            ParseOptions {
+                net_barrel_returns: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let liquefaction = netted.get_recipe("coal-liquefaction");
+        let flow = |flow: &[(rust_decimal::Decimal, Item)]| {
+            flow.iter()
+                .map(|(amount, item)| (item.name.clone(), *amount))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            flow(&liquefaction.ingredients),
+            vec![
+                ("coal".to_string(), dec!(10)),
+                ("steam".to_string(), dec!(50))
+            ]
+        );
+        assert_eq!(
+            flow(&liquefaction.results),
+            vec![
+                ("heavy-oil".to_string(), dec!(65)),
+                ("light-oil".to_string(), dec!(20)),
+                ("petroleum-gas".to_string(), dec!(10))
+            ]
+        );
+        assert!(liquefaction.catalysts.is_empty());
+    }
+
+    #[test]
+    fn test_amount_encodings() {
+        let recipe_with_amount = |amount: &str| {
+            format!(
+                r#"{{
+                    "light-oil-cracking": {{
+                        "name": "light-oil-cracking",
+                        "category": "chemistry",
+                        "energy": 2,
+                        "ingredients": [{{"name": "light-oil", "amount": {amount}}}],
+                        "products": [{{"name": "petroleum-gas", "amount": 20}}]
+                    }}
+                }}"#
+            )
+        };
+        let ingredient_amount = |amount: &str| {
+            DataSet::from_str(&recipe_with_amount(amount), &[])
+                .map(|data| data.get_recipe("light-oil-cracking").ingredients[0].0)
+        };
+
+        assert_eq!(ingredient_amount("30").unwrap(), dec!(30));
+        assert_eq!(ingredient_amount("0.1").unwrap(), dec!(0.1));
+        assert_eq!(ingredient_amount(r#""0.5""#).unwrap(), dec!(0.5));
+        assert_eq!(ingredient_amount(r#""1/4""#).unwrap(), dec!(0.25));
+
+        assert!(matches!(
+            ingredient_amount(r#""plenty""#),
+            Err(FactoryError::InvalidAmount(ref amount)) if amount == "plenty"
+        ));
+        assert!(matches!(
+            ingredient_amount(r#""1/0""#),
+            Err(FactoryError::InvalidAmount(ref amount)) if amount == "1/0"
+        ));
+
+        let catalysts_with_amount = |amount: &str| {
+            let recipes = format!(
+                r#"{{
+                    "kovarex-enrichment-process": {{
+                        "name": "kovarex-enrichment-process",
+                        "category": "centrifuging",
+                        "energy": 60,
+                        "ingredients": [{{"name": "uranium-235", "amount": 40}}],
+                        "products": [
+                            {{"name": "uranium-235", "amount": 41, "catalyst_amount": {amount}}}
+                        ]
+                    }}
+                }}"#
+            );
+            DataSet::from_str(&recipes, &[]).map(|data| {
+                data.get_recipe("kovarex-enrichment-process")
+                    .catalysts
+                    .iter()
+                    .map(|(amount, _)| *amount)
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(catalysts_with_amount("40").unwrap(), vec![dec!(40)]);
+        assert_eq!(catalysts_with_amount(r#""40""#).unwrap(), vec![dec!(40)]);
+        assert_eq!(catalysts_with_amount("0.5").unwrap(), vec![dec!(0.5)]);
+        assert!(catalysts_with_amount("0").unwrap().is_empty());
+        assert!(matches!(
+            catalysts_with_amount(r#""some""#),
+            Err(FactoryError::InvalidAmount(ref amount)) if amount == "some"
+        ));
+    }
+
+    #[test]
+    fn test_categories() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "iron-gear-wheel": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}]
+            },
+            "pipe": {
+                "name": "pipe",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 1}],
+                "products": [{"name": "pipe", "amount": 1}]
+            }
+        }"#;
+
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+
+        assert_eq!(data.get_recipe("iron-plate").category, "smelting");
+        assert_eq!(
+            data.categories(),
+            ["crafting", "smelting"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_catalog() {
+        let recipes = r#"{
+            "iron-gear-wheel": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}]
+            },
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            }
+        }"#;
+
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+
+        assert_eq!(
+            data.catalog(),
+            [
+                "items:",
+                "  iron-gear-wheel",
+                "  iron-ore (natural)",
+                "  iron-plate",
+                "recipes:",
+                "  iron-gear-wheel [Assembler]: 2 iron-plate -> 1 iron-gear-wheel",
+                "  iron-plate [Smelter]: 1 iron-ore -> 1 iron-plate",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_from_dir() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let naturals = ["iron-ore".to_string()];
+
+        let data = DataSet::from_dir(fixtures.join("recipe-dir"), &naturals).unwrap();
+        assert_eq!(
+            data.recipes
+                .iter()
+                .map(|recipe| recipe.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["iron-gear-wheel", "iron-plate"]
+        );
+        assert_eq!(data.items.len(), 3);
+        assert_eq!(data.natural_items(), vec![data.get_item("iron-ore")]);
+
+        let malformed = DataSet::from_dir(fixtures.join("recipe-dir-malformed"), &naturals);
+        assert!(matches!(
+            malformed,
+            Err(FactoryError::RecipeFileMalformed { ref file, .. }) if file.ends_with("pipe.json")
+        ));
+    }
+
+    #[test]
+    fn test_from_str_lenient() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "pipe": {
+                "name": "pipe",
+                "category": "crafting",
+                "ingredients": [{"name": "iron-plate", "amount": 1}]
+            },
+            "instant-gear": {
+                "name": "instant-gear",
+                "category": "crafting",
+                "energy": 0,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}]
+            }
+        }"#;
+
+        assert!(DataSet::from_str(recipes, &["iron-ore".to_string()]).is_err());
+
+        let (data, errors) = DataSet::from_str_lenient(recipes, &["iron-ore".to_string()]).unwrap();
+
+        assert_eq!(data.recipes.len(), 1);
+        assert_eq!(data.recipes[0].name, "iron-plate");
+        assert_eq!(data.items.len(), 2);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].key, "instant-gear");
+        assert!(matches!(
+            errors[0].error,
+            FactoryError::NonPositiveRecipeTime(_)
+        ));
+        assert_eq!(errors[1].key, "pipe");
+        assert!(matches!(errors[1].error, FactoryError::JsonMalformed(_)));
+    }
+
+    #[test]
+    fn test_factory_kind_for() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            }
+        }"#;
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+
+        assert_eq!(
+            data.factory_kind_for("iron-plate"),
+            Some(FactoryKind::Smelter)
+        );
+        assert_eq!(data.factory_kind_for("iron-ore"), None);
+    }
+
+    #[test]
+    fn test_produced_natural_is_demoted() {
+        let recipes = r#"{
+            "offshore-pump": {
+                "name": "offshore-pump",
+                "category": "crafting",
+                "energy": 1,
+                "ingredients": [],
+                "products": [{"name": "water", "amount": 1200}]
+            },
+            "steam": {
+                "name": "steam",
+                "category": "chemistry",
+                "energy": 1,
+                "ingredients": [{"name": "water", "amount": 60}],
+                "products": [{"name": "steam", "amount": 60}]
+            },
+            "empty-crude-oil-barrel": {
+                "name": "empty-crude-oil-barrel",
+                "category": "crafting-with-fluid",
+                "energy": 0.2,
+                "ingredients": [{"name": "crude-oil-barrel", "amount": 1}],
+                "products": [{"name": "crude-oil", "amount": 50}]
+            },
+            "fill-crude-oil-barrel": {
+                "name": "fill-crude-oil-barrel",
+                "category": "crafting-with-fluid",
+                "energy": 0.2,
+                "ingredients": [{"name": "crude-oil", "amount": 50}],
+                "products": [{"name": "crude-oil-barrel", "amount": 1}]
+            },
+            "iron-ore": {
+                "name": "iron-ore",
+                "category": "mining",
+                "energy": 1,
+                "ingredients": [],
+                "products": [{"name": "iron-ore", "amount": 1}]
+            }
+        }"#;
+        let naturals = [
+            "water".to_string(),
+            "iron-ore".to_string(),
+            "crude-oil".to_string(),
+        ];
+        let data = DataSet::from_str(recipes, &naturals).unwrap();
+
+        assert!(!data.get_item("water").natural);
+        assert_eq!(data.demoted_naturals(), ["water"]);
+        assert!(data.get_item("iron-ore").natural);
+        assert!(data.get_item("crude-oil").natural);
+        assert!(data
+            .recipes
+            .iter()
+            .flat_map(|recipe| recipe.ingredients.iter().chain(recipe.results.iter()))
+            .filter(|(_, item)| item.name == "water")
+            .all(|(_, item)| !item.natural));
+
+        let kept = DataSet::from_str_with_options(
+            recipes,
+            &naturals,
+            ParseOptions {
+                keep_produced_naturals: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(kept.get_item("water").natural);
+        assert!(kept.demoted_naturals().is_empty());
+        assert_eq!(
+            kept.produced_naturals()
+                .into_iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["water"]
+        );
+        assert!(data.produced_naturals().is_empty());
+    }
+
+    #[test]
+    fn test_add_recipe_keeps_existing_normalisation() {
+        let recipes = r#"{
+            "offshore-pump": {
+                "name": "offshore-pump",
+                "category": "crafting",
+                "energy": 1,
+                "ingredients": [],
+                "products": [{"name": "water", "amount": 1200}]
+            },
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            }
+        }"#;
+        let naturals = ["water".to_string(), "iron-ore".to_string()];
+        let mut data = DataSet::from_str_with_options(
+            recipes,
+            &naturals,
+            ParseOptions {
+                keep_produced_naturals: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(data.produced_naturals().len(), 1);
+
+        let copper_plate = |factory_kind: FactoryKind| Recipe {
+            name: "copper-plate".to_string(),
+            results: vec![(
+                dec!(1),
+                Item {
+                    name: "copper-plate".to_string(),
+                    natural: false,
+                },
+            )],
+            catalysts: vec![],
+            ingredients: vec![(
+                dec!(1),
+                Item {
+                    name: "copper-ore".to_string(),
+                    natural: true,
+                },
+            )],
+            time: Duration::from_secs_f64(3.2),
+            category: "smelting".to_string(),
+            factory_kind,
+            allows_productivity: true,
+            reversible: false,
+            version: None,
+        };
+        data.add_recipe(copper_plate(FactoryKind::Smelter)).unwrap();
+
+        assert_eq!(data.produced_naturals(), vec![data.get_item("water")]);
+        assert!(data.demoted_naturals().is_empty());
+
+        // Options only apply to the added recipes.
+        data.add_recipes_with_options(
+            vec![Recipe {
+                name: "burner-copper-plate".to_string(),
+                ..copper_plate(FactoryKind::Smelter)
+            }],
+            ParseOptions {
+                burner_buildings: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            data.get_recipe("burner-copper-plate").factory_kind,
+            FactoryKind::BurnerSmelter
+        );
+        assert_eq!(
+            data.get_recipe("iron-plate").factory_kind,
+            FactoryKind::Smelter
+        );
+        assert_eq!(data.produced_naturals(), vec![data.get_item("water")]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "iron-gear-wheel": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-gear-wheel", "amount": 1}]
+            }
+        }"#;
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+
+        assert_eq!(
+            data.to_dot(),
+            [
+                "digraph {",
+                r#"    "item:iron-gear-wheel" [label = "iron-gear-wheel", shape = ellipse]"#,
+                r#"    "item:iron-ore" [label = "iron-ore", shape = ellipse]"#,
+                r#"    "item:iron-plate" [label = "iron-plate", shape = ellipse]"#,
+                r#"    "recipe:iron-gear-wheel" [label = "iron-gear-wheel", shape = box]"#,
+                r#"    "recipe:iron-plate" [label = "iron-plate", shape = box]"#,
+                r#"    "item:iron-plate" -> "recipe:iron-gear-wheel" [label = "2"]"#,
+                r#"    "recipe:iron-gear-wheel" -> "item:iron-gear-wheel" [label = "1"]"#,
+                r#"    "item:iron-ore" -> "recipe:iron-plate" [label = "1"]"#,
+                r#"    "recipe:iron-plate" -> "item:iron-plate" [label = "1"]"#,
+                "}",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_recipes_with_dangling_outputs() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            },
+            "iron-gear-wheel": {
+                "name": "iron-gear-wheel",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 2}],
+                "products": [{"name": "iron-geer-wheel", "amount": 1}]
+            },
+            "iron-stick": {
+                "name": "iron-stick",
+                "category": "crafting",
+                "energy": 0.5,
+                "ingredients": [{"name": "iron-plate", "amount": 1}],
+                "products": [{"name": "iron-stick", "amount": 2}]
+            }
+        }"#;
+        let data = DataSet::from_str(recipes, &["iron-ore".to_string()]).unwrap();
+
+        assert_eq!(
+            data.recipes_with_dangling_outputs(),
+            vec![data.get_recipe("iron-gear-wheel")]
+        );
+    }
 }
@@ -1,12 +1,14 @@
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::io::Write as _;
+use std::ops::ControlFlow;
 
 use std::path::Path;
-use std::process::{Command, Stdio};
-use std::{cmp, fmt, fs};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use std::{cmp, fmt, fs, io};
 
-use crate::entities::{Item, ItemAmount, Recipe};
+use crate::entities::{FactoryKind, Item, ItemAmount, ItemName, Recipe, RecipeName};
 use crate::error::FactoryResult;
 use crate::prelude::FactoryError;
 use crate::traits::DataSource;
@@ -15,11 +17,25 @@ use itertools::Itertools;
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::NodeIndex;
 use petgraph::prelude::*;
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::Reversed;
+use rust_decimal::prelude::ToPrimitive as _;
+use rust_decimal::Decimal;
+use serde_json::json;
+
+mod planning;
+
+pub use planning::{ConsumptionPlan, ModuleConfig, PlanDeficit, ProductionReport, RecipePlan};
 
 #[derive(Debug, Clone)]
 pub struct CraftingGraph<'data> {
-    pub data: DiGraph<Node<'data>, ItemAmount>,
+    data: DiGraph<Node<'data>, ItemAmount>,
     natural_items: Vec<&'data Item>,
+    /// Index of the first item node with each name, kept up to date by [`Self::add_node`] and
+    /// [`Self::reindex`], so name lookups don't scan the whole graph.
+    item_indices: HashMap<String, NodeIndex>,
+    /// Same as `item_indices`, for recipe nodes.
+    recipe_indices: HashMap<String, NodeIndex>,
 }
 
 impl cmp::PartialEq for CraftingGraph<'_> {
@@ -37,15 +53,47 @@ where
     D: DataSource,
 {
     fn from(data: &'data D) -> Self {
-        CraftingGraph {
-            data: DiGraph::new(),
-            natural_items: data.natural_items(),
-        }
+        CraftingGraph::with_data(DiGraph::new(), data.natural_items())
     }
 }
 
 type Tier = usize;
 
+/// Options controlling which recipes [`CraftingGraph::from_dataset_with_options`] puts in the graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphOptions {
+    /// Include mining recipes ([`FactoryKind::is_mining`]), which produce natural items.
+    /// They become the roots of the graph, so natural items are outputs of mining instead of
+    /// being given for free. Off by default.
+    pub include_mining: bool,
+    /// Include the inverse recipes ([`Recipe::is_reverse`]), like the ones added by
+    /// [`crate::data::DataSet::with_reverse_recipes`], which turn results back into ingredients,
+    /// modeling recycling loops. Each one is a node of its own, so a reversible recipe and its
+    /// inverse form a cycle only through their items. Off by default, which leaves them out.
+    pub reverse_recipes: bool,
+}
+
+/// Options controlling how [`CraftingGraph::get_crafting_trees_with_options`] builds the trees.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeOptions {
+    /// Let a recipe which consumes the item it produces, like Kovarex enrichment, loop back into
+    /// that item instead of leaving a dangling copy of it, so the tree models the loop in steady
+    /// state. The loop edge carries the consumed amount, so the recipe's net output is the
+    /// difference of the two edges between the item and the recipe. Off by default, which keeps
+    /// every tree acyclic.
+    pub allow_self_loops: bool,
+    /// Also branch toward recipes making the item only as a byproduct, i.e. whose
+    /// [`Recipe::main_product`] is a different item. Recipes without a main product, like oil
+    /// processing, produce each of their results equally and are always followed. Off by default,
+    /// so e.g. a tree for an item isn't routed through a recipe meant for another one.
+    pub allow_byproducts: bool,
+    /// Keep at most this many partial trees waiting to be expanded, dropping the ones with the
+    /// highest tier score once there are more, which turns the search into a beam search. Bounds
+    /// the memory taken by high-branching targets, but the results may miss some solutions,
+    /// even better ones. Unbounded by default.
+    pub max_frontier: Option<usize>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Node<'data> {
     Item(&'data Item, Tier),
@@ -53,6 +101,13 @@ pub enum Node<'data> {
 }
 
 impl Node<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Node::Item(item, _) => &item.name,
+            Node::Recipe(recipe, _) => &recipe.name,
+        }
+    }
+
     fn get_tier(&self) -> Tier {
         match self {
             Node::Item(_, tier) | Node::Recipe(_, tier) => *tier,
@@ -73,7 +128,12 @@ impl fmt::Display for Node<'_> {
                 f.write_str(&format!("{} [{}]", item.name, tier))?;
             }
             Node::Recipe(recipe, tier) => {
-                f.write_str(&format!("{} | {:?} [{}]", &recipe.name, &recipe.time, tier))?;
+                f.write_str(&format!(
+                    "{} ({}) [{}]",
+                    &recipe.name,
+                    format_duration(recipe.time),
+                    tier
+                ))?;
             }
         }
 
@@ -99,6 +159,22 @@ impl cmp::Ord for CraftingGraph<'_> {
     }
 }
 
+/// Whether an item flows into a recipe as an ingredient, or out of it as a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FlowDirection {
+    Input,
+    Output,
+}
+
+/// Flat description of a single edge of [`CraftingGraph`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EdgeInfo {
+    pub recipe: RecipeName,
+    pub item: ItemName,
+    pub direction: FlowDirection,
+    pub amount: ItemAmount,
+}
+
 impl<'data> CraftingGraph<'data> {
     /// Create a directed graph of items and recipes.
     /// Each node is either item or recipe, which alternate between one another. In other words, there
@@ -112,33 +188,90 @@ impl<'data> CraftingGraph<'data> {
     /// And such, if the edge is
     /// - (Recipe -> Item), then it has a weight that describes how much items can be crafted from this recipe.
     /// - (Item -> Recipe), then it's weight describes the amount of items needed for target recipe.
-    pub fn iter_nodes(&self) -> impl Iterator<Item = Node> {
+    pub fn iter_nodes(&self) -> impl Iterator<Item = Node<'data>> + '_ {
         self.data.node_weights().copied()
     }
 
-    pub fn get_item_node(&self, item_name: &str) -> Node {
-        self.iter_nodes()
-            .find(|item| matches!(item, Node::Item(Item {name, .. }, _) if name == item_name))
-            .unwrap_or_else(|| panic!("Recipe {item_name} not found"))
+    /// Read-only view of the underlying petgraph graph, for algorithms this crate doesn't provide.
+    /// Mutation goes through [`CraftingGraph`] methods only, so that invariants like alternating
+    /// item and recipe nodes or consistent tiers can't be broken from outside.
+    pub fn graph(&self) -> &DiGraph<Node<'data>, ItemAmount> {
+        &self.data
+    }
+
+    /// Get the node of the item, or None if the item isn't in the graph.
+    pub fn try_get_item_node(&self, item_name: &str) -> Option<Node<'data>> {
+        self.get_item_idx_from_name(item_name)
+            .map(|idx| self.data[idx])
+    }
+
+    /// Same as [`Self::try_get_item_node`], but panics if the item isn't in the graph.
+    pub fn get_item_node(&self, item_name: &str) -> Node<'data> {
+        self.try_get_item_node(item_name)
+            .unwrap_or_else(|| panic!("Item {item_name} not found"))
+    }
+
+    /// Get the node of the recipe, or None if the recipe isn't in the graph.
+    pub fn try_get_recipe_node(&self, recipe_name: &str) -> Option<Node<'data>> {
+        self.get_recipe_idx_from_name(recipe_name)
+            .map(|idx| self.data[idx])
     }
 
-    pub fn get_recipe_node(&self, recipe_name: &str) -> Node {
-        self.iter_nodes()
-            .find(|recipe| matches!(recipe, Node::Recipe(Recipe {name, .. }, _) if name == recipe_name))
+    /// Same as [`Self::try_get_recipe_node`], but panics if the recipe isn't in the graph.
+    pub fn get_recipe_node(&self, recipe_name: &str) -> Node<'data> {
+        self.try_get_recipe_node(recipe_name)
             .unwrap_or_else(|| panic!("Recipe {recipe_name} not found"))
     }
 
+    /// Get the building crafting the recipe, or None if the recipe isn't in the graph.
+    pub fn factory_kind_for(&self, recipe_name: &str) -> Option<FactoryKind> {
+        match self.try_get_recipe_node(recipe_name)? {
+            Node::Recipe(recipe, _) => Some(recipe.factory_kind.clone()),
+            Node::Item(..) => None,
+        }
+    }
+
     pub fn from_dataset<D: DataSource>(dataset: &'data D) -> Self {
+        Self::from_dataset_with_options(dataset, GraphOptions::default())
+    }
+
+    /// Same as [`Self::from_dataset`], but lets the caller tweak which recipes enter the graph.
+    pub fn from_dataset_with_options<D: DataSource>(
+        dataset: &'data D,
+        options: GraphOptions,
+    ) -> Self {
         let mut graph = Self::from(dataset);
 
         let mut current_indices: Vec<NodeIndex> = vec![];
         let mut visited = HashSet::new();
 
-        for natural in &graph.natural_items {
-            let idx = graph.data.add_node(Node::Item(natural, 0));
+        for natural in graph.natural_items.clone() {
+            let idx = graph.add_node(Node::Item(natural, 0));
             current_indices.push(idx);
         }
 
+        if options.include_mining {
+            let mining_recipes = dataset
+                .iter_recipes()
+                .filter(|rec| rec.factory_kind.is_mining());
+
+            for recipe in mining_recipes {
+                let idx = graph.add_node(Node::Recipe(recipe, 0));
+                current_indices.push(idx);
+            }
+        }
+
+        // Recipes consuming each item, in data set order, with the amount they consume.
+        let mut consumers: HashMap<&str, Vec<(&'data Recipe, ItemAmount)>> = HashMap::new();
+        for recipe in dataset.iter_recipes().filter(|rec| {
+            (options.include_mining || !rec.factory_kind.is_mining())
+                && (options.reverse_recipes || !rec.is_reverse())
+        }) {
+            for (name, amount) in recipe.ingredient_map() {
+                consumers.entry(name).or_default().push((recipe, amount));
+            }
+        }
+
         while let Some(current_idx) = current_indices.pop() {
             if visited.contains(&current_idx) {
                 continue;
@@ -146,34 +279,18 @@ impl<'data> CraftingGraph<'data> {
 
             match graph.data[current_idx] {
                 Node::Item(item, tier) => {
-                    let recipes_depending_on_item = dataset.iter_recipes().filter(|rec| {
-                        rec.ingredients
-                            .iter()
-                            .any(|(_, ingredient)| ingredient.name == item.name)
-                    });
+                    let recipes_depending_on_item = consumers
+                        .get(item.name.as_str())
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
 
-                    for recipe in recipes_depending_on_item {
+                    for &(recipe, weight) in recipes_depending_on_item {
                         let mut maybe_recipe_idx = graph.get_recipe_idx_from_name(&recipe.name);
 
-                        let recipe_idx = maybe_recipe_idx.get_or_insert_with(|| {
-                            graph.data.add_node(Node::Recipe(recipe, tier + 1))
-                        });
+                        let recipe_idx = maybe_recipe_idx
+                            .get_or_insert_with(|| graph.add_node(Node::Recipe(recipe, tier + 1)));
 
-                        let input_amount =
-                            recipe
-                                .ingredients
-                                .iter()
-                                .find_map(|(amount, ingredient_item)| {
-                                    if item.name == ingredient_item.name {
-                                        Some(*amount)
-                                    } else {
-                                        None
-                                    }
-                                });
-
-                        if let Some(weight) = input_amount {
-                            graph.data.update_edge(current_idx, *recipe_idx, weight);
-                        }
+                        graph.data.update_edge(current_idx, *recipe_idx, weight);
 
                         current_indices.push(*recipe_idx);
                     }
@@ -184,7 +301,7 @@ impl<'data> CraftingGraph<'data> {
                         let mut maybe_item_idx = graph.get_item_idx_from_name(&item.name);
 
                         let item_idx = maybe_item_idx
-                            .get_or_insert_with(|| graph.data.add_node(Node::Item(item, tier + 1)));
+                            .get_or_insert_with(|| graph.add_node(Node::Item(item, tier + 1)));
 
                         graph.data.add_edge(current_idx, *item_idx, *amount);
                         current_indices.push(*item_idx);
@@ -200,17 +317,85 @@ impl<'data> CraftingGraph<'data> {
         graph
     }
 
+    /// Build the graph directly from its nodes and edges, skipping the traversal done by [`Self::from_dataset`].
+    /// Edges are `(from, to, amount)` triples, where `from` and `to` are positions in `nodes`.
+    /// Tiers are taken as they are stored in the nodes.
+    /// Returns [`FactoryError::EdgeEndpointOutOfRange`] if any edge points outside of `nodes`.
+    pub fn from_parts(
+        nodes: Vec<Node<'data>>,
+        edges: Vec<(usize, usize, ItemAmount)>,
+        natural_items: Vec<&'data Item>,
+    ) -> FactoryResult<Self> {
+        let mut data = DiGraph::with_capacity(nodes.len(), edges.len());
+        for node in nodes {
+            data.add_node(node);
+        }
+
+        for (from, to, amount) in edges {
+            let node_count = data.node_count();
+            if from >= node_count || to >= node_count {
+                return Err(FactoryError::EdgeEndpointOutOfRange {
+                    from,
+                    to,
+                    node_count,
+                });
+            }
+
+            data.add_edge(NodeIndex::new(from), NodeIndex::new(to), amount);
+        }
+
+        Ok(Self::with_data(data, natural_items))
+    }
+
+    /// Check that every edge carries a strictly positive amount. Anything else points to a parsing
+    /// or data bug, which would corrupt every ratio computed from the graph.
+    /// Returns [`FactoryError::NonPositiveAmount`] for the first offending edge.
+    pub fn validate_edges(&self) -> FactoryResult<()> {
+        let Some(edge) = self
+            .data
+            .edge_references()
+            .find(|edge| *edge.weight() <= Decimal::ZERO)
+        else {
+            return Ok(());
+        };
+
+        let (recipe, item) = match (self.data[edge.source()], self.data[edge.target()]) {
+            (item @ Node::Item(..), recipe @ Node::Recipe(..)) => (recipe, item),
+            (from, to) => (from, to),
+        };
+
+        Err(FactoryError::NonPositiveAmount {
+            recipe: recipe.name().to_string(),
+            item: item.name().to_string(),
+        })
+    }
+
+    /// Recompute the tier of every node: natural items are tier 0, a recipe is one tier past its
+    /// deepest ingredient and an item one tier past its lowest-tier recipe.
+    /// Recipes without ingredients, like mining, are tier 0 too, and natural items mined by them
+    /// (see [`GraphOptions::include_mining`]) are one tier past their lowest-tier mining recipe.
     pub fn adjust_tiers(&mut self) {
         let mut current_indices: VecDeque<NodeIndex> = VecDeque::new();
         let mut visited = HashSet::new();
 
-        for natural in &self.natural_items {
+        for natural in self.natural_items.clone() {
             let idx = self
                 .get_node_idx(Node::Item(natural, 0))
-                .unwrap_or_else(|| self.data.add_node(Node::Item(natural, 0)));
+                .unwrap_or_else(|| self.add_node(Node::Item(natural, 0)));
 
-            current_indices.push_back(idx);
+            // Mined items are reached through their mining recipes, once those have their tier.
+            if self.mining_recipe_idxs(idx).is_empty() {
+                current_indices.push_back(idx);
+            }
         }
+        current_indices.extend(self.data.node_indices().filter(|&idx| {
+            matches!(self.data[idx], Node::Recipe(..))
+                && self
+                    .data
+                    .neighbors_directed(idx, Direction::Incoming)
+                    .next()
+                    .is_none()
+        }));
 
         while let Some(current_idx) = current_indices.pop_front() {
             if visited.contains(&current_idx) {
@@ -227,10 +412,18 @@ impl<'data> CraftingGraph<'data> {
                         .min()
                         .unwrap_or(1);
 
-                    self.data[current_idx].set_tier(recipes_tier_min + 1);
-
+                    // Natural items are the roots of the graph unless they're mined, everything
+                    // else is one step past its cheapest recipe.
                     if item.natural {
-                        self.data[current_idx].set_tier(1);
+                        let mined_tier_min = self
+                            .mining_recipe_idxs(current_idx)
+                            .into_iter()
+                            .map(|recipe_idx| self.data[recipe_idx].get_tier())
+                            .min();
+                        self.data[current_idx]
+                            .set_tier(mined_tier_min.map_or(0, |tier| tier.saturating_add(1)));
+                    } else {
+                        self.data[current_idx].set_tier(recipes_tier_min.saturating_add(1));
                     }
 
                     current_indices.extend(
@@ -251,13 +444,16 @@ impl<'data> CraftingGraph<'data> {
                         }
 
                         Some(ingredients) => {
-                            let ingredients_tier_sum = ingredients
+                            // A recipe is one step past its deepest ingredient. Summing the
+                            // ingredient tiers instead made tiers explode (and overflow on deep
+                            // modded graphs) for recipes with many ingredients.
+                            let tier = ingredients
                                 .into_iter()
-                                .map(|idx| self.data[idx])
-                                .map(|node| node.get_tier())
-                                .sum::<Tier>();
+                                .map(|idx| self.data[idx].get_tier())
+                                .max()
+                                .map_or(0, |tier_max| tier_max.saturating_add(1));
 
-                            self.data[current_idx].set_tier(ingredients_tier_sum + 1);
+                            self.data[current_idx].set_tier(tier);
 
                             current_indices.extend(
                                 self.get_results_for_recipe_idxs(self.data[current_idx])
@@ -274,6 +470,111 @@ impl<'data> CraftingGraph<'data> {
         }
     }
 
+    /// Record the current tier of every node, so it can be brought back with [`Self::restore_tiers`]
+    /// without running [`Self::adjust_tiers`] again.
+    pub fn snapshot_tiers(&self) -> Vec<(NodeIndex, Tier)> {
+        self.data
+            .node_indices()
+            .map(|idx| (idx, self.data[idx].get_tier()))
+            .collect()
+    }
+
+    /// Set node tiers back to the ones recorded by [`Self::snapshot_tiers`].
+    /// Returns [`FactoryError::NodeIndexOutOfRange`] if the snapshot refers to a node which no longer
+    /// exists, in which case no tier is changed.
+    pub fn restore_tiers(&mut self, snapshot: Vec<(NodeIndex, Tier)>) -> FactoryResult<()> {
+        let node_count = self.data.node_count();
+        if let Some((idx, _)) = snapshot.iter().find(|(idx, _)| idx.index() >= node_count) {
+            return Err(FactoryError::NodeIndexOutOfRange {
+                index: idx.index(),
+                node_count,
+            });
+        }
+
+        for (idx, tier) in snapshot {
+            self.data[idx].set_tier(tier);
+        }
+
+        Ok(())
+    }
+
+    /// Get the tier of the item with the given name, or None if it isn't in the graph.
+    pub fn item_tier(&self, item_name: &str) -> Option<Tier> {
+        self.try_get_item_node(item_name)
+            .map(|node| node.get_tier())
+    }
+
+    /// Get the tier of the recipe with the given name, or None if it isn't in the graph.
+    pub fn recipe_tier(&self, recipe_name: &str) -> Option<Tier> {
+        self.try_get_recipe_node(recipe_name)
+            .map(|node| node.get_tier())
+    }
+
+    /// Explain the tier of the item, as assigned by [`Self::adjust_tiers`]: the recipe it takes its
+    /// tier from and the tiers of that recipe's ingredients, like
+    ///
+    /// ```text
+    /// iron-gear-wheel: tier 4, one past its lowest-tier recipe
+    ///   iron-gear-wheel: tier 3, one past its deepest ingredient
+    ///     iron-plate: tier 2
+    /// ```
+    ///
+    /// Returns None if the item isn't in the graph.
+    pub fn explain_tier(&self, item_name: &str) -> Option<String> {
+        let item_idx = self.get_item_idx_from_name(item_name)?;
+        let Node::Item(item, tier) = self.data[item_idx] else {
+            return None;
+        };
+
+        if item.natural {
+            return Some(format!(
+                "{item_name}: tier {tier}, natural items start at 0"
+            ));
+        }
+
+        let Some(recipe_idx) = self
+            .data
+            .neighbors_directed(item_idx, Direction::Incoming)
+            .min_by_key(|idx| self.data[*idx].get_tier())
+        else {
+            return Some(format!("{item_name}: tier {tier}, no recipe produces it"));
+        };
+        let Node::Recipe(recipe, recipe_tier) = self.data[recipe_idx] else {
+            return None;
+        };
+
+        let ingredients = self
+            .data
+            .neighbors_directed(recipe_idx, Direction::Incoming)
+            .filter(|idx| {
+                recipe
+                    .ingredients
+                    .iter()
+                    .any(|(_, ingredient)| ingredient.name == self.data[*idx].name())
+            })
+            .map(|idx| self.data[idx])
+            .sorted_by(|node1, node2| {
+                node2
+                    .get_tier()
+                    .cmp(&node1.get_tier())
+                    .then_with(|| node1.name().cmp(node2.name()))
+            })
+            .map(|node| format!("    {}: tier {}", node.name(), node.get_tier()));
+
+        Some(
+            [
+                format!("{item_name}: tier {tier}, one past its lowest-tier recipe"),
+                format!(
+                    "  {}: tier {recipe_tier}, one past its deepest ingredient",
+                    recipe.name
+                ),
+            ]
+            .into_iter()
+            .chain(ingredients)
+            .join("\n"),
+        )
+    }
+
     /// Get all indices of item nodes that are direct input items to the recipe provided.
     /// If the node is not a recipe or it doesn't exist in graph, None is returned.
     pub fn get_ingredients_for_recipe_idx(&self, node: Node) -> Option<Vec<NodeIndex>> {
@@ -347,6 +648,18 @@ impl<'data> CraftingGraph<'data> {
     }
 
     pub fn get_node_idx(&self, target_node: Node) -> Option<NodeIndex> {
+        let indexed = match target_node {
+            Node::Item(item, _) => self.item_indices.get(&item.name),
+            Node::Recipe(recipe, _) => self.recipe_indices.get(&recipe.name),
+        };
+        match indexed {
+            // No node of that kind carries the name at all.
+            None => return None,
+            Some(&idx) if self.data[idx] == target_node => return Some(idx),
+            // Only the first node with each name is indexed, later ones need a scan.
+            Some(_) => {}
+        }
+
         self.data
             .node_weights()
             .position(|node| *node == target_node)
@@ -354,45 +667,147 @@ impl<'data> CraftingGraph<'data> {
     }
 
     pub fn get_item_idx_from_name(&self, item_name: &str) -> Option<NodeIndex> {
-        self.data
-            .node_weights()
-            .position(|node| match node {
-                Node::Item(Item { name, .. }, _) => item_name == name,
-                _ => false,
-            })
-            .map(|raw_idx| NodeIndex::from(raw_idx as u32))
+        self.item_indices.get(item_name).copied()
     }
 
     pub fn get_recipe_idx_from_name(&self, recipe_name: &str) -> Option<NodeIndex> {
+        self.recipe_indices.get(recipe_name).copied()
+    }
+
+    /// Graph over `data`, with the name indices built from its nodes.
+    fn with_data(data: DiGraph<Node<'data>, ItemAmount>, natural_items: Vec<&'data Item>) -> Self {
+        let mut graph = Self {
+            data,
+            natural_items,
+            item_indices: HashMap::new(),
+            recipe_indices: HashMap::new(),
+        };
+        graph.reindex();
+
+        graph
+    }
+
+    /// Get indices of the mining recipes producing the item at `idx`.
+    fn mining_recipe_idxs(&self, idx: NodeIndex) -> Vec<NodeIndex> {
         self.data
-            .node_weights()
-            .position(|node| match node {
-                Node::Recipe(Recipe { name, .. }, _) => recipe_name == name,
-                _ => false,
+            .neighbors_directed(idx, Direction::Incoming)
+            .filter(|&recipe_idx| match self.data[recipe_idx] {
+                Node::Recipe(recipe, _) => recipe.factory_kind.is_mining(),
+                Node::Item(..) => false,
             })
-            .map(|raw_idx| NodeIndex::from(raw_idx as u32))
+            .collect()
+    }
+
+    /// Add the node to the graph, indexing its name unless an earlier node already has it.
+    fn add_node(&mut self, node: Node<'data>) -> NodeIndex {
+        let idx = self.data.add_node(node);
+        let indices = match node {
+            Node::Item(..) => &mut self.item_indices,
+            Node::Recipe(..) => &mut self.recipe_indices,
+        };
+        indices.entry(node.name().to_string()).or_insert(idx);
+
+        idx
+    }
+
+    /// Rebuild the name indices from scratch, needed after nodes were removed, as removing a node
+    /// moves the last one into its place.
+    fn reindex(&mut self) {
+        self.item_indices.clear();
+        self.recipe_indices.clear();
+
+        for idx in self.data.node_indices() {
+            let node = self.data[idx];
+            let indices = match node {
+                Node::Item(..) => &mut self.item_indices,
+                Node::Recipe(..) => &mut self.recipe_indices,
+            };
+            indices.entry(node.name().to_string()).or_insert(idx);
+        }
     }
 
     /// Starting at the target node, get a list of possible crafting paths an item can have.
     /// Each time an item can be crafted from multiple (N) recipes, this graph will branch into N graphs, which will be processed
     /// further, until each crafting tree is complete.
-    /// Passing [`Node::Recipe`] as target will consider the concrete recipe as a starting point, meanwhile
-    /// [`Node::Item`] will consider every recipe which result in this item.
+    /// Passing [`Node::Recipe`] as target will consider the concrete recipe as a starting point: every tree is
+    /// rooted at that recipe and only branches below it. Meanwhile [`Node::Item`] will consider every recipe
+    /// which result in this item.
+    /// Natural items are leaves of the trees, unless the graph mines them (see
+    /// [`GraphOptions::include_mining`]), in which case the trees branch into their mining recipes.
     /// If target doesn't exist in graph, then None is returned.
     pub fn get_crafting_trees(
         &'data self,
         target: Node<'data>,
         max_number_of_solutions: usize,
+    ) -> Option<Vec<Self>> {
+        self.get_crafting_trees_with_options(
+            target,
+            max_number_of_solutions,
+            TreeOptions::default(),
+        )
+    }
+
+    /// Same as [`Self::get_crafting_trees`], but lets the caller tweak how the trees are built.
+    pub fn get_crafting_trees_with_options(
+        &'data self,
+        target: Node<'data>,
+        max_number_of_solutions: usize,
+        options: TreeOptions,
     ) -> Option<Vec<Self>> {
         let mut complete_subgraphs: Vec<Self> = vec![];
+        if max_number_of_solutions == 0 {
+            self.get_node_idx(target)?;
+            return Some(complete_subgraphs);
+        }
+
+        self.visit_crafting_trees(target, options, |tree| {
+            complete_subgraphs.push(tree);
+            if complete_subgraphs.len() >= max_number_of_solutions {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+
+        Some(complete_subgraphs)
+    }
+
+    /// Call `f` with every complete crafting tree of the target, one by one, until it returns
+    /// [`ControlFlow::Break`]. Trees are found in the same order as by
+    /// [`Self::get_crafting_trees`], but none of them is kept around, so the memory use doesn't
+    /// grow with the number of solutions. Limiting the number of solutions is up to `f`.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn for_each_crafting_tree(
+        &'data self,
+        target: Node<'data>,
+        f: impl FnMut(&Self) -> ControlFlow<()>,
+    ) -> Option<()> {
+        self.for_each_crafting_tree_with_options(target, TreeOptions::default(), f)
+    }
 
+    /// Same as [`Self::for_each_crafting_tree`], but lets the caller tweak how the trees are built,
+    /// like [`Self::get_crafting_trees_with_options`].
+    pub fn for_each_crafting_tree_with_options(
+        &'data self,
+        target: Node<'data>,
+        options: TreeOptions,
+        mut f: impl FnMut(&Self) -> ControlFlow<()>,
+    ) -> Option<()> {
+        self.visit_crafting_trees(target, options, |tree| f(&tree))
+    }
+
+    /// Build the crafting trees of the target as described by [`Self::get_crafting_trees`], handing
+    /// every complete one to `f` until it returns [`ControlFlow::Break`].
+    fn visit_crafting_trees(
+        &'data self,
+        target: Node<'data>,
+        options: TreeOptions,
+        mut f: impl FnMut(Self) -> ControlFlow<()>,
+    ) -> Option<()> {
         let target_idx = self.get_node_idx(target)?;
 
-        let mut first_tree = Self {
-            data: DiGraph::new(),
-            natural_items: self.natural_items.clone(),
-        };
-        let subgraph_head_idx = first_tree.data.add_node(target);
+        let mut first_tree = Self::with_data(DiGraph::new(), self.natural_items.clone());
+        let subgraph_head_idx = first_tree.add_node(target);
 
         let mut processing_queue: BinaryHeap<(Self, Vec<(NodeIndex, NodeIndex)>)> =
             BinaryHeap::from([(first_tree, vec![(target_idx, subgraph_head_idx)])]);
@@ -400,14 +815,12 @@ impl<'data> CraftingGraph<'data> {
         while let Some((mut subgraph, mut processing_indices)) = processing_queue.pop() {
             if processing_indices.is_empty() {
                 println!("Found possibility with len {}", subgraph.data.node_count());
-                complete_subgraphs.push(subgraph);
+                if f(subgraph).is_break() {
+                    break;
+                }
                 continue;
             }
 
-            if complete_subgraphs.len() >= max_number_of_solutions {
-                break;
-            }
-
             let (current_graph_idx, current_subgraph_idx) = processing_indices.pop()?;
 
             match subgraph.data[current_subgraph_idx] {
@@ -421,17 +834,37 @@ impl<'data> CraftingGraph<'data> {
                             recipe_idxs
                         });
 
-                    if item.natural {
-                        processing_queue.push((subgraph, processing_indices));
-                        continue;
-                    }
-
-                    for recipe_graph_idx in recipe_graph_idxs? {
+                    // Natural items are leaves, unless they're mined in the graph, in which case
+                    // only their mining recipes produce them.
+                    let recipe_graph_idxs = if item.natural {
+                        let mining_idxs = self.mining_recipe_idxs(current_graph_idx);
+                        if mining_idxs.is_empty() {
+                            processing_queue.push((subgraph, processing_indices));
+                            continue;
+                        }
+                        recipe_graph_idxs?
+                            .into_iter()
+                            .filter(|idx| mining_idxs.contains(idx))
+                            .collect()
+                    } else {
+                        recipe_graph_idxs?
+                    };
+
+                    for recipe_graph_idx in recipe_graph_idxs {
                         let recipe = self.data[recipe_graph_idx];
 
+                        if let Node::Recipe(producer, _) = recipe {
+                            let is_byproduct = producer
+                                .main_product()
+                                .is_some_and(|product| product.name != item.name);
+                            if is_byproduct && !options.allow_byproducts {
+                                continue;
+                            }
+                        }
+
                         let mut branched_subgraph = subgraph.clone();
 
-                        let added_recipe_subgraph_idx = branched_subgraph.data.add_node(recipe);
+                        let added_recipe_subgraph_idx = branched_subgraph.add_node(recipe);
 
                         let recipe_output = self
                             .data
@@ -459,16 +892,36 @@ impl<'data> CraftingGraph<'data> {
 
                     for item_graph_idx in item_graph_idxs? {
                         let item = self.data[item_graph_idx];
+                        let input_amount = self
+                            .data
+                            .edges_connecting(item_graph_idx, current_graph_idx)
+                            .map(|edge| *edge.weight())
+                            .next()?;
+
+                        // The recipe consumes the very item it was picked to produce, so it feeds
+                        // back into that item instead of starting an endless branch.
+                        let produced_item_subgraph_idx = subgraph
+                            .data
+                            .neighbors_directed(current_subgraph_idx, Direction::Outgoing)
+                            .find(|&idx| subgraph.data[idx] == item);
 
-                        let added_item_subgraph_idx = subgraph.data.add_node(item);
+                        if let (true, Some(produced_item_subgraph_idx)) =
+                            (options.allow_self_loops, produced_item_subgraph_idx)
+                        {
+                            subgraph.data.add_edge(
+                                produced_item_subgraph_idx,
+                                current_subgraph_idx,
+                                input_amount,
+                            );
+                            continue;
+                        }
+
+                        let added_item_subgraph_idx = subgraph.add_node(item);
 
                         subgraph.data.add_edge(
                             added_item_subgraph_idx,
                             current_subgraph_idx,
-                            self.data
-                                .edges_connecting(item_graph_idx, current_graph_idx)
-                                .map(|edge| *edge.weight())
-                                .next()?,
+                            input_amount,
                         );
 
                         if subgraph.copy_of_node_is_present_in_ancestors(item, current_subgraph_idx)
@@ -482,9 +935,36 @@ impl<'data> CraftingGraph<'data> {
                     processing_queue.push((subgraph, processing_indices));
                 }
             }
+
+            if let Some(max_frontier) = options.max_frontier {
+                if processing_queue.len() > max_frontier {
+                    // Sorted ascending, so the partial trees with the highest tier score come first.
+                    let mut frontier = processing_queue.into_sorted_vec();
+                    frontier.drain(..frontier.len() - max_frontier);
+                    processing_queue = BinaryHeap::from(frontier);
+                }
+            }
         }
 
-        Some(complete_subgraphs)
+        Some(())
+    }
+
+    /// Same as [`Self::get_crafting_trees`], but keeps at most `max_frontier` partial trees around,
+    /// see [`TreeOptions::max_frontier`]. Some solutions may be missed.
+    pub fn get_crafting_trees_with_frontier(
+        &'data self,
+        target: Node<'data>,
+        max_number_of_solutions: usize,
+        max_frontier: usize,
+    ) -> Option<Vec<Self>> {
+        self.get_crafting_trees_with_options(
+            target,
+            max_number_of_solutions,
+            TreeOptions {
+                max_frontier: Some(max_frontier),
+                ..TreeOptions::default()
+            },
+        )
     }
 
     //
@@ -498,199 +978,3006 @@ impl<'data> CraftingGraph<'data> {
         self.clone()
     }
 
-    pub fn indices_to_nodes(&self, indices: &[NodeIndex]) -> Vec<Node> {
+    pub fn indices_to_nodes(&self, indices: &[NodeIndex]) -> Vec<Node<'data>> {
         indices.iter().map(|idx| self.data[*idx]).collect()
     }
 
-    fn copy_of_node_is_present_in_ancestors(
-        &self,
-        node: Node,
-        parent_of_node_idx: NodeIndex,
-    ) -> bool {
-        let mut dfs = Dfs::new(&self.data, parent_of_node_idx);
-        while let Some(idx) = dfs.next(&self.data) {
-            if self.data[idx] == node {
-                return true;
-            }
-        }
+    /// Count recipe nodes per [`FactoryKind`]. On a crafting tree this tells how mixed its buildings
+    /// are, e.g. the share of the most common kind scores how simple the tree is to build.
+    pub fn factory_kind_distribution(&self) -> HashMap<FactoryKind, usize> {
+        self.data
+            .node_weights()
+            .filter_map(|node| match node {
+                Node::Recipe(recipe, _) => Some(recipe.factory_kind.clone()),
+                Node::Item(..) => None,
+            })
+            .counts()
+    }
 
-        false
+    /// Get every recipe that has to be available to reach the given tier, i.e. recipes with tier at
+    /// most `tier`, ordered by tier and then by name. Handy for staged build guides.
+    pub fn recipes_up_to_tier(&self, tier: Tier) -> Vec<&'data Recipe> {
+        self.data
+            .node_weights()
+            .filter_map(|node| match node {
+                Node::Recipe(recipe, recipe_tier) if *recipe_tier <= tier => {
+                    Some((*recipe_tier, *recipe))
+                }
+                _ => None,
+            })
+            .sorted_by(|(tier1, recipe1), (tier2, recipe2)| {
+                tier1
+                    .cmp(tier2)
+                    .then_with(|| recipe1.name.cmp(&recipe2.name))
+            })
+            .map(|(_, recipe)| recipe)
+            .collect()
     }
 
-    pub fn to_dot(&self) -> String {
-        // Config::_Incomplete gives the best drawing despite being WIP
-        format!(
-            "{}",
-            Dot::with_config(&self.data, &[Config::_Incomplete(())])
-        )
+    /// Get the `(ingredient_count, product_count)` of a recipe, i.e. the amount of distinct items
+    /// flowing in and out of its node.
+    /// If the recipe doesn't exist in graph, None is returned.
+    pub fn recipe_degree(&self, recipe_name: &str) -> Option<(usize, usize)> {
+        let recipe_idx = self.get_recipe_idx_from_name(recipe_name)?;
+
+        Some((
+            self.data
+                .neighbors_directed(recipe_idx, Direction::Incoming)
+                .count(),
+            self.data
+                .neighbors_directed(recipe_idx, Direction::Outgoing)
+                .count(),
+        ))
     }
 
-    pub fn save_as_svg(&self, file_name: impl AsRef<Path>) -> FactoryResult<()> {
-        let dot = self.to_dot();
-        let mut cmd = Command::new("dot")
-            .arg("-Tsvg")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+    /// Rank items by the number of recipes consuming them, i.e. the out-degree of their nodes.
+    /// Items are sorted by descending consumer count, ties are broken by item name.
+    pub fn items_by_consumer_count(&self) -> Vec<(&'data Item, usize)> {
+        self.data
+            .node_indices()
+            .filter_map(|idx| match self.data[idx] {
+                Node::Item(item, _) => Some((
+                    item,
+                    self.data
+                        .neighbors_directed(idx, Direction::Outgoing)
+                        .count(),
+                )),
+                Node::Recipe(..) => None,
+            })
+            .sorted_by(|(item1, count1), (item2, count2)| {
+                count2.cmp(count1).then_with(|| item1.name.cmp(&item2.name))
+            })
+            .collect()
+    }
 
-        {
-            let mut stdin = cmd.stdin.take().ok_or(FactoryError::CommandSpawn(
-                "Failed to take stdin".to_string(),
-            ))?;
+    /// Get items produced by exactly one recipe, together with that recipe. These are the parts of
+    /// a plan without any choice to make. Natural items and items with alternative recipes are
+    /// left out.
+    pub fn forced_recipes(&self) -> HashMap<&'data Item, &'data Recipe> {
+        self.data
+            .node_indices()
+            .filter_map(|idx| {
+                let Node::Item(item, _) = self.data[idx] else {
+                    return None;
+                };
+                if item.natural {
+                    return None;
+                }
 
-            stdin.write_all(dot.as_bytes())?;
-        }
+                let (producer_idx,) = self
+                    .data
+                    .neighbors_directed(idx, Direction::Incoming)
+                    .unique()
+                    .collect_tuple()?;
+                match self.data[producer_idx] {
+                    Node::Recipe(recipe, _) => Some((item, recipe)),
+                    Node::Item(..) => None,
+                }
+            })
+            .collect()
+    }
 
-        let output = cmd.wait_with_output()?;
+    /// Find the recipe where production chains of both items converge, i.e. the highest-tier
+    /// recipe that both items (transitively) depend on.
+    /// If either item doesn't exist in graph or they share no dependencies, None is returned.
+    pub fn shared_dependency(&self, item_a: &str, item_b: &str) -> Option<&'data Recipe> {
+        let ancestors_a = self.ancestor_indices(self.get_item_idx_from_name(item_a)?);
+        let ancestors_b = self.ancestor_indices(self.get_item_idx_from_name(item_b)?);
+
+        ancestors_a
+            .intersection(&ancestors_b)
+            .filter_map(|idx| match self.data[*idx] {
+                Node::Recipe(recipe, tier) => Some((tier, recipe)),
+                Node::Item(..) => None,
+            })
+            .max_by(|(tier1, recipe1), (tier2, recipe2)| {
+                tier1
+                    .cmp(tier2)
+                    .then_with(|| recipe2.name.cmp(&recipe1.name))
+            })
+            .map(|(_, recipe)| recipe)
+    }
 
-        if !output.stderr.is_empty() {
-            println!("stderr: {}", std::str::from_utf8(&output.stderr)?);
-        }
+    /// Get the set of distinct natural items the target (transitively) depends on, across all of
+    /// its alternative recipes. A natural target depends on itself only.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn raw_resource_types(&self, target: Node<'data>) -> Option<HashSet<&'data Item>> {
+        let target_idx = self.get_node_idx(target)?;
 
-        let mut file = fs::File::create(file_name)?;
-        file.write_all(&output.stdout)?;
+        Some(
+            self.ancestor_indices(target_idx)
+                .into_iter()
+                .chain([target_idx])
+                .filter_map(|idx| match self.data[idx] {
+                    Node::Item(item, _) if item.natural => Some(item),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
 
-        Ok(())
+    /// Get every recipe that (transitively) supplies the ingredients of the given recipe, across all
+    /// alternative recipes of each ingredient, i.e. its whole supply chain.
+    /// If the recipe doesn't exist in graph, None is returned.
+    pub fn upstream_recipes(&self, recipe_name: &str) -> Option<HashSet<&'data Recipe>> {
+        let recipe_idx = self.get_recipe_idx_from_name(recipe_name)?;
+
+        Some(
+            self.ancestor_indices(recipe_idx)
+                .into_iter()
+                .filter_map(|idx| match self.data[idx] {
+                    Node::Recipe(recipe, _) if recipe.name != recipe_name => Some(recipe),
+                    _ => None,
+                })
+                .collect(),
+        )
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::time::Duration;
+    /// Get the depth of the production chain: the largest number of recipes which have to run one
+    /// after another to craft any item from natural resources. Each item is crafted by its
+    /// shallowest recipe and a recipe has to wait for its deepest ingredient. Natural items have depth
+    /// 0, so do graphs without any craftable item.
+    /// Unlike the tiers, this is well defined for graphs with loops like oil cracking: a recipe only
+    /// counts once all of its ingredients can be crafted without it.
+    pub fn production_depth(&self) -> usize {
+        self.item_depths().into_values().max().unwrap_or_default()
+    }
 
-    use itertools::Itertools;
-    use rust_decimal::Decimal;
-    use rust_decimal_macros::dec;
+    /// Get the depth of a recipe: the number of recipes which have to run one after another, this
+    /// one included, to craft it from natural resources. That's the depth of the deepest ingredient
+    /// (see [`Self::production_depth`]) plus one, so recipes using only natural items have depth 1.
+    /// Unlike the tier, ingredients don't add up, e.g. electronic-circuit has depth 3 but tier 5.
+    /// Returns None if the recipe doesn't exist in graph or some ingredient can't be crafted.
+    pub fn recipe_depth(&self, recipe_name: &str) -> Option<usize> {
+        let recipe_idx = self.get_recipe_idx_from_name(recipe_name)?;
+        let depths = self.item_depths();
 
-    use crate::{
-        entities::{FactoryKind, Item, Recipe},
-        traits::{self, DataSource},
-    };
+        self.data
+            .neighbors_directed(recipe_idx, Direction::Incoming)
+            .map(|idx| depths.get(&idx).copied())
+            .try_fold(0, |deepest, depth| Some(cmp::max(deepest, depth?)))
+            .map(|deepest| deepest + 1)
+    }
 
-    use super::{CraftingGraph, Node, Tier};
+    /// Get items which can't be crafted from natural resources, because some recipe on every way
+    /// to them needs an ingredient nobody can make. Items are ordered by name.
+    pub fn unreachable_items(&self) -> Vec<&'data Item> {
+        let depths = self.item_depths();
 
-    struct DataSetMock {
-        items: Vec<Item>,
-        recipes: Vec<Recipe>,
+        self.data
+            .node_indices()
+            .filter(|idx| !depths.contains_key(idx))
+            .filter_map(|idx| match self.data[idx] {
+                Node::Item(item, _) => Some(item),
+                Node::Recipe(..) => None,
+            })
+            .sorted_by(|item1, item2| item1.name.cmp(&item2.name))
+            .collect()
     }
 
-    impl traits::DataSource for DataSetMock {
-        fn from_str(
-            _recipes_str: &str,
-            _natural_item_names: &[String],
-        ) -> crate::error::FactoryResult<Self>
-        where
-            Self: std::marker::Sized,
-        {
-            Ok(Self::new())
-        }
+    /// Get recipes which can be supplied from natural resources, but not without the given item
+    /// somewhere upstream: every way of crafting their ingredients passes through the item, or
+    /// they consume it directly. Recipes are ordered by name.
+    /// If the item doesn't exist in graph, nothing is returned.
+    pub fn recipes_requiring(&self, item_name: &str) -> Vec<&'data Recipe> {
+        let Some(item_idx) = self.get_item_idx_from_name(item_name) else {
+            return vec![];
+        };
+        let excluded = HashSet::from([item_idx]);
+
+        let supplied = |depths: &HashMap<NodeIndex, usize>, recipe_idx: NodeIndex| {
+            self.data
+                .neighbors_directed(recipe_idx, Direction::Incoming)
+                .all(|idx| depths.contains_key(&idx))
+        };
+        let depths = self.item_depths();
+        let depths_without = self.item_depths_without(&excluded);
+
+        self.data
+            .node_indices()
+            .filter(|idx| supplied(&depths, *idx) && !supplied(&depths_without, *idx))
+            .filter_map(|idx| match self.data[idx] {
+                Node::Recipe(recipe, _) => Some(recipe),
+                Node::Item(..) => None,
+            })
+            .unique()
+            .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+            .collect()
+    }
+
+    /// Check whether the target can be produced from natural resources using only the `allowed`
+    /// recipes, e.g. the ones unlocked so far. A recipe target has to be allowed itself.
+    /// Returns false if target doesn't exist in graph.
+    pub fn is_craftable(&self, target: Node<'data>, allowed: &HashSet<String>) -> bool {
+        let Some(target_idx) = self.get_node_idx(target) else {
+            return false;
+        };
+
+        let mut available: HashSet<NodeIndex> = self
+            .data
+            .node_indices()
+            .filter(|idx| matches!(self.data[*idx], Node::Item(item, _) if item.natural))
+            .collect();
+        let runnable = |available: &HashSet<NodeIndex>, recipe_idx: NodeIndex| {
+            matches!(self.data[recipe_idx], Node::Recipe(recipe, _) if allowed.contains(&recipe.name))
+                && self
+                    .data
+                    .neighbors_directed(recipe_idx, Direction::Incoming)
+                    .all(|idx| available.contains(&idx))
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for recipe_idx in self.data.node_indices() {
+                if !runnable(&available, recipe_idx) {
+                    continue;
+                }
+
+                for item_idx in self
+                    .data
+                    .neighbors_directed(recipe_idx, Direction::Outgoing)
+                {
+                    changed |= available.insert(item_idx);
+                }
+            }
+        }
+
+        match self.data[target_idx] {
+            Node::Item(..) => available.contains(&target_idx),
+            Node::Recipe(..) => runnable(&available, target_idx),
+        }
+    }
+
+    /// Get recipes which have to be unlocked on top of `already_unlocked` to craft the target from
+    /// natural resources, e.g. the research still needed for it.
+    /// Every item is crafted by the producer needing the fewest new recipes, which finds the
+    /// smallest set whenever the chains of different ingredients don't share new recipes, and a
+    /// small one otherwise. For a [`Node::Recipe`] target, the recipe itself is part of the set
+    /// unless it's unlocked already.
+    /// Returns None if target doesn't exist in graph or can't be crafted even with every recipe.
+    pub fn recipes_to_unlock(
+        &self,
+        target: Node<'data>,
+        already_unlocked: &HashSet<String>,
+    ) -> Option<HashSet<&'data Recipe>> {
+        let target_idx = self.get_node_idx(target)?;
+
+        let mut needed: HashMap<NodeIndex, HashSet<NodeIndex>> = self
+            .data
+            .node_indices()
+            .filter(|idx| matches!(self.data[*idx], Node::Item(item, _) if item.natural))
+            .map(|idx| (idx, HashSet::new()))
+            .collect();
+        let recipe_needs = |needed: &HashMap<NodeIndex, HashSet<NodeIndex>>,
+                            recipe_idx: NodeIndex| {
+            let Node::Recipe(recipe, _) = self.data[recipe_idx] else {
+                return None;
+            };
+            let mut recipes = HashSet::new();
+            for ingredient_idx in self
+                .data
+                .neighbors_directed(recipe_idx, Direction::Incoming)
+            {
+                recipes.extend(needed.get(&ingredient_idx)?);
+            }
+            if !already_unlocked.contains(&recipe.name) {
+                recipes.insert(recipe_idx);
+            }
+
+            Some(recipes)
+        };
+
+        // Sets only ever get smaller once assigned, so relaxing recipes until nothing changes
+        // terminates.
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for recipe_idx in self.data.node_indices() {
+                let Some(recipes) = recipe_needs(&needed, recipe_idx) else {
+                    continue;
+                };
+
+                for item_idx in self
+                    .data
+                    .neighbors_directed(recipe_idx, Direction::Outgoing)
+                {
+                    if needed
+                        .get(&item_idx)
+                        .is_some_and(|current| current.len() <= recipes.len())
+                    {
+                        continue;
+                    }
+                    needed.insert(item_idx, recipes.clone());
+                    changed = true;
+                }
+            }
+        }
+
+        let recipes = match self.data[target_idx] {
+            Node::Item(..) => needed.remove(&target_idx)?,
+            Node::Recipe(..) => recipe_needs(&needed, target_idx)?,
+        };
+
+        Some(
+            recipes
+                .into_iter()
+                .filter_map(|idx| match self.data[idx] {
+                    Node::Recipe(recipe, _) => Some(recipe),
+                    Node::Item(..) => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Remove the recipe together with its edges. Items it used to produce or consume stay in the
+    /// graph, tiers are left as they are.
+    /// Node indices obtained before the removal may no longer be valid.
+    /// If the recipe doesn't exist in graph, None is returned.
+    pub fn remove_recipe(&mut self, recipe_name: &str) -> Option<&'data Recipe> {
+        let recipe_idx = self.get_recipe_idx_from_name(recipe_name)?;
+
+        let removed = self.data.remove_node(recipe_idx)?;
+        self.reindex();
+
+        match removed {
+            Node::Recipe(recipe, _) => Some(recipe),
+            Node::Item(..) => None,
+        }
+    }
+
+    /// Merge item nodes sharing the same name into the one with the lowest index, moving the edges of
+    /// the duplicates over to it. When both already connect to the same recipe, the existing edge is
+    /// kept as is, so the flow isn't counted twice. The duplicates are then removed.
+    /// Node indices obtained before the merge may no longer be valid.
+    pub fn dedup_item_nodes(&mut self) {
+        let duplicates = self
+            .data
+            .node_indices()
+            .filter_map(|idx| match self.data[idx] {
+                Node::Item(item, _) => Some((item.name.as_str(), idx)),
+                Node::Recipe(..) => None,
+            })
+            .into_group_map()
+            .into_values()
+            .filter(|indices| indices.len() > 1)
+            .collect_vec();
+
+        let mut removed = vec![];
+        for indices in duplicates {
+            let (canonical, rest) = indices
+                .split_first()
+                .expect("Groups hold at least two indices");
+
+            for duplicate in rest {
+                let outgoing = self
+                    .data
+                    .edges_directed(*duplicate, Direction::Outgoing)
+                    .map(|edge| (edge.target(), *edge.weight()))
+                    .collect_vec();
+                let incoming = self
+                    .data
+                    .edges_directed(*duplicate, Direction::Incoming)
+                    .map(|edge| (edge.source(), *edge.weight()))
+                    .collect_vec();
+
+                for (target, amount) in outgoing {
+                    if !self.data.contains_edge(*canonical, target) {
+                        self.data.add_edge(*canonical, target, amount);
+                    }
+                }
+                for (source, amount) in incoming {
+                    if !self.data.contains_edge(source, *canonical) {
+                        self.data.add_edge(source, *canonical, amount);
+                    }
+                }
+
+                removed.push(*duplicate);
+            }
+        }
+
+        // Removing a node moves the last one into its place, so going from the highest index down
+        // never moves a node which is still to be removed.
+        for idx in removed.into_iter().sorted().rev() {
+            self.data.remove_node(idx);
+        }
+        self.reindex();
+    }
+
+    /// Get items which can be crafted now, but not anymore once the recipe is removed, i.e. what
+    /// can no longer be built if the recipe is forbidden. The graph itself is left untouched.
+    /// Items are ordered by name. If the recipe doesn't exist in graph, nothing is returned.
+    pub fn reachability_impact(&self, recipe_name: &str) -> Vec<&'data Item> {
+        let mut without_recipe = self.clone();
+        if without_recipe.remove_recipe(recipe_name).is_none() {
+            return vec![];
+        }
+
+        let unreachable_before: HashSet<&str> = self
+            .unreachable_items()
+            .into_iter()
+            .map(|item| item.name.as_str())
+            .collect();
+
+        without_recipe
+            .unreachable_items()
+            .into_iter()
+            .filter(|item| !unreachable_before.contains(item.name.as_str()))
+            .collect()
+    }
+
+    /// Get recipes which can be removed one at a time without making any item uncraftable, because
+    /// alternatives cover everything they produce, see [`Self::reachability_impact`]. Removing all
+    /// of them at once may still cut items off. Recipes are ordered by name.
+    /// Every recipe is checked on its own copy of the graph, so this takes
+    /// O(recipes × graph size) and is meant for trimming data sets, not for calling in a loop.
+    pub fn redundant_for_reachability(&self) -> Vec<&'data Recipe> {
+        self.data
+            .node_weights()
+            .filter_map(|node| match node {
+                Node::Recipe(recipe, _) => Some(*recipe),
+                Node::Item(..) => None,
+            })
+            .filter(|recipe| self.reachability_impact(&recipe.name).is_empty())
+            .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+            .collect()
+    }
+
+    /// Get a copy of the graph where the natural items listed in each group are replaced by the
+    /// group's item, e.g. every ore merged into `raw-ore`. Edges of the merged items are moved over
+    /// to the group item, summing the amounts when several members flow into the same recipe.
+    /// Names which aren't natural items of the graph are ignored.
+    /// Groups are keyed by their items rather than by names, because nodes only borrow the items
+    /// they stand for and the group items aren't part of any data set: the caller owns them, for as
+    /// long as the merged graph is used. They become natural items of the graph.
+    /// Only the graph is rewired, the [`Recipe`]s in its nodes keep naming the original items.
+    /// Queries working from the edges, like [`Self::raw_materials`], [`Self::net_production`],
+    /// [`Self::cost_attribution`], [`Self::verify_plan`] and [`Self::surplus`], see the group items,
+    /// while the ones reading the recipes themselves, like [`Self::bus_lanes`] and
+    /// [`Self::inserter_requirements`], still list the members.
+    pub fn with_merged_naturals(&self, groups: &HashMap<&'data Item, Vec<String>>) -> Self {
+        let mut merged = self.clone();
+        let mut merged_away = vec![];
+
+        for (&group_item, member_names) in groups
+            .iter()
+            .sorted_by(|(item1, _), (item2, _)| item1.name.cmp(&item2.name))
+        {
+            let members = merged
+                .data
+                .node_indices()
+                .filter(|idx| match merged.data[*idx] {
+                    Node::Item(item, _) => item.natural && member_names.contains(&item.name),
+                    Node::Recipe(..) => false,
+                })
+                .collect_vec();
+
+            if members.is_empty() {
+                continue;
+            }
+
+            let tier = members
+                .iter()
+                .map(|idx| merged.data[*idx].get_tier())
+                .min()
+                .unwrap_or_default();
+            let group_idx = merged.add_node(Node::Item(group_item, tier));
+
+            let mut outgoing: HashMap<NodeIndex, ItemAmount> = HashMap::new();
+            let mut incoming: HashMap<NodeIndex, ItemAmount> = HashMap::new();
+            for member in &members {
+                for edge in merged.data.edges_directed(*member, Direction::Outgoing) {
+                    *outgoing.entry(edge.target()).or_default() += *edge.weight();
+                }
+                for edge in merged.data.edges_directed(*member, Direction::Incoming) {
+                    *incoming.entry(edge.source()).or_default() += *edge.weight();
+                }
+            }
+
+            for (recipe_idx, amount) in outgoing {
+                merged.data.add_edge(group_idx, recipe_idx, amount);
+            }
+            for (recipe_idx, amount) in incoming {
+                merged.data.add_edge(recipe_idx, group_idx, amount);
+            }
+
+            merged
+                .natural_items
+                .retain(|item| !member_names.contains(&item.name));
+            merged.natural_items.push(group_item);
+            merged_away.extend(members);
+        }
+
+        // Removing a node moves the last one into its place, so going from the highest index down
+        // never moves a node which is still to be removed.
+        for idx in merged_away.into_iter().sorted().rev() {
+            merged.data.remove_node(idx);
+        }
+        merged.reindex();
+
+        merged
+    }
+
+    /// Get the production depth of every item which can be crafted from natural resources, see
+    /// [`Self::production_depth`]. Items missing from the map can't be crafted at all.
+    fn item_depths(&self) -> HashMap<NodeIndex, usize> {
+        self.item_depths_without(&HashSet::new())
+    }
+
+    /// Same as [`Self::item_depths`], but the `excluded` items are treated as if nothing could
+    /// supply them.
+    fn item_depths_without(&self, excluded: &HashSet<NodeIndex>) -> HashMap<NodeIndex, usize> {
+        let mut depths: HashMap<NodeIndex, usize> = self
+            .data
+            .node_indices()
+            .filter(|idx| matches!(self.data[*idx], Node::Item(item, _) if item.natural))
+            .filter(|idx| !excluded.contains(idx))
+            .map(|idx| (idx, 0))
+            .collect();
+
+        // Depths only ever decrease once set, so relaxing recipes until nothing changes terminates.
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for recipe_idx in self.data.node_indices() {
+                if !matches!(self.data[recipe_idx], Node::Recipe(..)) {
+                    continue;
+                }
+
+                let Some(deepest_ingredient) = self
+                    .data
+                    .neighbors_directed(recipe_idx, Direction::Incoming)
+                    .map(|idx| depths.get(&idx).copied())
+                    .try_fold(0, |deepest, depth| Some(cmp::max(deepest, depth?)))
+                else {
+                    continue;
+                };
+
+                for item_idx in self
+                    .data
+                    .neighbors_directed(recipe_idx, Direction::Outgoing)
+                    .filter(|idx| !excluded.contains(idx))
+                {
+                    let depth = depths.entry(item_idx).or_insert(usize::MAX);
+                    if deepest_ingredient + 1 < *depth {
+                        *depth = deepest_ingredient + 1;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        depths
+    }
+
+    /// Get indices of every node from which `start` can be reached, excluding `start` itself.
+    fn ancestor_indices(&self, start: NodeIndex) -> HashSet<NodeIndex> {
+        let reversed = Reversed(&self.data);
+        let mut dfs = Dfs::new(reversed, start);
+        let mut ancestors = HashSet::new();
+
+        while let Some(idx) = dfs.next(reversed) {
+            if idx != start {
+                ancestors.insert(idx);
+            }
+        }
+
+        ancestors
+    }
+
+    /// List every edge of the graph as `(recipe, item, direction, amount)`, sorted by recipe name,
+    /// then item name and direction. Handy for eyeballing suspicious amounts in a data set.
+    pub fn edge_report(&self) -> Vec<EdgeInfo> {
+        self.data
+            .edge_references()
+            .filter_map(
+                |edge| match (self.data[edge.source()], self.data[edge.target()]) {
+                    (Node::Item(item, _), Node::Recipe(recipe, _)) => Some(EdgeInfo {
+                        recipe: recipe.name.clone(),
+                        item: item.name.clone(),
+                        direction: FlowDirection::Input,
+                        amount: *edge.weight(),
+                    }),
+                    (Node::Recipe(recipe, _), Node::Item(item, _)) => Some(EdgeInfo {
+                        recipe: recipe.name.clone(),
+                        item: item.name.clone(),
+                        direction: FlowDirection::Output,
+                        amount: *edge.weight(),
+                    }),
+                    _ => None,
+                },
+            )
+            .sorted()
+            .collect()
+    }
+
+    /// Partition recipes into groups that share no item (neither ingredient nor result) with recipes
+    /// from any other group. Each group is an independent subsystem of the factory, which can be
+    /// laid out and run in parallel with the rest.
+    /// Recipes inside a group, as well as the groups themselves, are ordered by recipe name.
+    pub fn independent_recipe_groups(&self) -> Vec<Vec<&'data Recipe>> {
+        let mut components = UnionFind::new(self.data.node_count());
+        for edge in self.data.edge_references() {
+            components.union(edge.source().index(), edge.target().index());
+        }
+
+        self.data
+            .node_indices()
+            .filter_map(|idx| match self.data[idx] {
+                Node::Recipe(recipe, _) => Some((components.find(idx.index()), recipe)),
+                Node::Item(..) => None,
+            })
+            .into_group_map()
+            .into_values()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+                    .collect_vec()
+            })
+            .sorted_by(|group1, group2| group1[0].name.cmp(&group2[0].name))
+            .collect()
+    }
+
+    /// Split the graph into its weakly connected components, e.g. the independent product families
+    /// left after filtering a data set. Every component keeps the tiers of its nodes and only the
+    /// natural items it contains. Components are ordered by the position of their first node.
+    pub fn components(&self) -> Vec<Self> {
+        let mut components = UnionFind::new(self.data.node_count());
+        for edge in self.data.edge_references() {
+            components.union(edge.source().index(), edge.target().index());
+        }
+
+        self.data
+            .node_indices()
+            .map(|idx| components.find(idx.index()))
+            .unique()
+            .map(|root| self.induced_subgraph(|idx| components.find(idx.index()) == root))
+            .collect()
+    }
+
+    /// Get the subgraph made of the node with the given name (an item, or a recipe if no item is
+    /// named so) and everything it's crafted from, across all alternative recipes. Nodes keep
+    /// their tiers, so nothing is recomputed.
+    /// If no node has the name, None is returned.
+    pub fn subgraph_for(&self, target_name: &str) -> Option<Self> {
+        let target_idx = self
+            .get_item_idx_from_name(target_name)
+            .or_else(|| self.get_recipe_idx_from_name(target_name))?;
+        let mut closure = self.ancestor_indices(target_idx);
+        closure.insert(target_idx);
+
+        Some(self.induced_subgraph(|idx| closure.contains(&idx)))
+    }
+
+    /// Same as [`Self::subgraph_for`], but for several targets at once, keeping the union of their
+    /// closures. Unknown names are ignored. Cheaper than building a graph from a filtered data set,
+    /// as tiers don't have to be adjusted again.
+    pub fn focus(&self, targets: &[&str]) -> Self {
+        let closure: HashSet<NodeIndex> = targets
+            .iter()
+            .filter_map(|target_name| {
+                self.get_item_idx_from_name(target_name)
+                    .or_else(|| self.get_recipe_idx_from_name(target_name))
+            })
+            .flat_map(|target_idx| {
+                let mut closure = self.ancestor_indices(target_idx);
+                closure.insert(target_idx);
+                closure
+            })
+            .collect();
+
+        self.induced_subgraph(|idx| closure.contains(&idx))
+    }
+
+    /// Copy of the graph keeping only the nodes for which `keep` holds, the edges between them
+    /// and the natural items among them.
+    fn induced_subgraph(&self, keep: impl Fn(NodeIndex) -> bool) -> Self {
+        let data = self.data.filter_map(
+            |idx, node| keep(idx).then_some(*node),
+            |_, amount| Some(*amount),
+        );
+        let natural_items = self
+            .natural_items
+            .iter()
+            .filter(|natural| {
+                data.node_weights().any(|node| match node {
+                    Node::Item(item, _) => item == *natural,
+                    Node::Recipe(..) => false,
+                })
+            })
+            .copied()
+            .collect();
+
+        Self::with_data(data, natural_items)
+    }
+
+    /// Get items which are articulation points of the graph treated as undirected, i.e. items whose
+    /// removal splits the graph into more pieces, disconnecting some products from their raw
+    /// resources. They are the single points of failure of the production chain.
+    /// Items are ordered by name.
+    pub fn bottleneck_items(&self) -> Vec<&'data Item> {
+        self.articulation_points()
+            .into_iter()
+            .filter_map(|idx| match self.data[idx] {
+                Node::Item(item, _) => Some(item),
+                Node::Recipe(..) => None,
+            })
+            .sorted_by(|item1, item2| item1.name.cmp(&item2.name))
+            .collect()
+    }
+
+    /// Find cut vertices of the undirected view of the graph with Tarjan's low-link algorithm.
+    /// The depth-first search keeps its own stack, so long production chains can't overflow the
+    /// call stack.
+    fn articulation_points(&self) -> HashSet<NodeIndex> {
+        struct Frame {
+            idx: NodeIndex,
+            parent: Option<NodeIndex>,
+            neighbors: Vec<NodeIndex>,
+            next_neighbor: usize,
+            children: usize,
+        }
+
+        struct Search {
+            discovery: Vec<Option<usize>>,
+            low: Vec<usize>,
+            timer: usize,
+        }
+
+        impl Search {
+            fn enter(
+                &mut self,
+                graph: &DiGraph<Node, ItemAmount>,
+                idx: NodeIndex,
+                parent: Option<NodeIndex>,
+            ) -> Frame {
+                self.discovery[idx.index()] = Some(self.timer);
+                self.low[idx.index()] = self.timer;
+                self.timer += 1;
+
+                Frame {
+                    idx,
+                    parent,
+                    neighbors: graph.neighbors_undirected(idx).unique().collect(),
+                    next_neighbor: 0,
+                    children: 0,
+                }
+            }
+        }
+
+        let node_count = self.data.node_count();
+        let mut search = Search {
+            discovery: vec![None; node_count],
+            low: vec![0; node_count],
+            timer: 0,
+        };
+        let mut points = HashSet::new();
+
+        for root in self.data.node_indices() {
+            if search.discovery[root.index()].is_some() {
+                continue;
+            }
+            let mut stack = vec![search.enter(&self.data, root, None)];
+
+            while let Some(frame) = stack.last_mut() {
+                let idx = frame.idx;
+
+                if let Some(&neighbor) = frame.neighbors.get(frame.next_neighbor) {
+                    frame.next_neighbor += 1;
+
+                    match search.discovery[neighbor.index()] {
+                        None => {
+                            frame.children += 1;
+                            let child = search.enter(&self.data, neighbor, Some(idx));
+                            stack.push(child);
+                        }
+                        Some(discovered) if Some(neighbor) != frame.parent => {
+                            search.low[idx.index()] = search.low[idx.index()].min(discovered);
+                        }
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+
+                let Some(frame) = stack.pop() else {
+                    break;
+                };
+                match stack.last() {
+                    Some(parent) => {
+                        let parent_idx = parent.idx;
+                        search.low[parent_idx.index()] =
+                            search.low[parent_idx.index()].min(search.low[idx.index()]);
+
+                        if parent.parent.is_some()
+                            && search.low[idx.index()]
+                                >= search.discovery[parent_idx.index()].unwrap_or_default()
+                        {
+                            points.insert(parent_idx);
+                        }
+                    }
+                    None if frame.children > 1 => {
+                        points.insert(idx);
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        points
+    }
+
+    fn copy_of_node_is_present_in_ancestors(
+        &self,
+        node: Node,
+        parent_of_node_idx: NodeIndex,
+    ) -> bool {
+        let mut dfs = Dfs::new(&self.data, parent_of_node_idx);
+        while let Some(idx) = dfs.next(&self.data) {
+            if self.data[idx] == node {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Get the ID under which the node appears in exports: `item:<name>` for items and
+    /// `recipe:<name>` for recipes, e.g. `item:iron-plate` or `recipe:electronic-circuit`.
+    /// Unlike a [`NodeIndex`], it doesn't depend on the order in which the graph was built, so it
+    /// stays the same across runs and export formats.
+    pub fn node_id(&self, node: Node) -> String {
+        match node {
+            Node::Item(item, _) => format!("item:{}", item.name),
+            Node::Recipe(recipe, _) => format!("recipe:{}", recipe.name),
+        }
+    }
+
+    /// Whether the graph has fewer than two nodes or no edges at all, so drawing it shows next to
+    /// nothing. That almost always means something upstream filtered too much.
+    pub fn is_degenerate(&self) -> bool {
+        self.data.node_count() < 2 || self.data.edge_count() == 0
+    }
+
+    /// Render the graph in DOT. Every node carries its [`Self::node_id`] as the `id` attribute,
+    /// which Graphviz keeps in the SVG it draws.
+    /// A graph which [`Self::is_degenerate`] is rendered as well, telling it apart is up to the
+    /// caller.
+    pub fn to_dot(&self) -> String {
+        // Config::_Incomplete gives the best drawing despite being WIP
+        format!(
+            "{}",
+            Dot::with_attr_getters(
+                &self.data,
+                &[Config::_Incomplete(())],
+                &|_, _| String::new(),
+                &|_, (_, node)| format!("id = {:?}", self.node_id(*node)),
+            )
+        )
+    }
+
+    /// Same as [`Self::to_dot`], but items are drawn as ellipses and recipes as boxes filled with
+    /// the color of their [`FactoryKind`].
+    /// With `with_legend`, a separate cluster explaining the shapes and colors is added, so the
+    /// drawing stays readable on its own.
+    pub fn to_dot_styled(&self, with_legend: bool) -> String {
+        let dot = format!(
+            "{}",
+            Dot::with_attr_getters(
+                &self.data,
+                &[Config::_Incomplete(())],
+                &|_, _| String::new(),
+                &|_, (_, node)| match node {
+                    Node::Item(..) => format!("id = {:?}, shape = ellipse", self.node_id(*node)),
+                    Node::Recipe(recipe, _) => format!(
+                        "id = {:?}, shape = box, style = filled, fillcolor = \"{}\"",
+                        self.node_id(*node),
+                        factory_kind_color(&recipe.factory_kind)
+                    ),
+                },
+            )
+        );
+
+        if !with_legend {
+            return dot;
+        }
+
+        let mut legend = vec![
+            "    subgraph cluster_legend {".to_string(),
+            "        label = \"Legend\"".to_string(),
+            "        legend_item [label = \"item\", shape = ellipse]".to_string(),
+            "        legend_recipe [label = \"recipe\", shape = box]".to_string(),
+        ];
+        legend.extend(FACTORY_KIND_COLORS.iter().map(|(kind, color)| {
+            format!(
+                "        legend_{kind:?} [label = \"{}\", shape = box, style = filled, fillcolor = \"{color}\"]",
+                kind.building_name()
+            )
+        }));
+        legend.push("    }".to_string());
+
+        // Dot output ends with the closing brace of the graph, the legend goes right before it.
+        let graph_end = dot.rfind('}').unwrap_or(dot.len());
+        format!(
+            "{}{}\n{}",
+            &dot[..graph_end],
+            legend.join("\n"),
+            &dot[graph_end..]
+        )
+    }
+
+    /// Render the graph as plain text for comparing against expected output in tests: one line per
+    /// node with its [`Self::node_id`], tier and whether it's natural, followed by one line per edge
+    /// with its amount. Both parts are sorted, so the output doesn't depend on the order in which
+    /// nodes and edges were added:
+    /// ```text
+    /// item:iron-ore natural tier=0
+    /// recipe:iron-plate tier=1
+    /// item:iron-ore -> recipe:iron-plate 1
+    /// ```
+    pub fn to_snapshot(&self) -> String {
+        let nodes = self
+            .data
+            .node_weights()
+            .map(|node| match node {
+                Node::Item(item, tier) if item.natural => {
+                    format!("{} natural tier={tier}", self.node_id(*node))
+                }
+                Node::Item(_, tier) | Node::Recipe(_, tier) => {
+                    format!("{} tier={tier}", self.node_id(*node))
+                }
+            })
+            .sorted();
+        let edges = self
+            .data
+            .edge_references()
+            .map(|edge| {
+                format!(
+                    "{} -> {} {}",
+                    self.node_id(self.data[edge.source()]),
+                    self.node_id(self.data[edge.target()]),
+                    edge.weight().normalize()
+                )
+            })
+            .sorted();
+
+        nodes.chain(edges).join("\n")
+    }
+
+    /// Export machine counts as a block-based JSON document resembling what calculator mods such as
+    /// Helmod or Factory Planner import: one block per recipe, ordered by recipe name, with the
+    /// recipe, its machine count and the building (name and [`FactoryKind`]) it's crafted in. Blocks
+    /// are identified by the [`Self::node_id`] of their recipe.
+    /// This is a documented approximation, not the mods' exact (compressed) exchange string format:
+    /// ```json
+    /// {"blocks": [{"id": "recipe:iron-plate", "name": "iron-plate", "recipe": "iron-plate", "count": 4.0,
+    ///              "factory": {"name": "electric-furnace", "type": "Smelter"}}]}
+    /// ```
+    pub fn to_helmod_json(&self, machine_counts: &HashMap<&Recipe, Decimal>) -> String {
+        let blocks = machine_counts
+            .iter()
+            .sorted_by(|(recipe1, _), (recipe2, _)| recipe1.name.cmp(&recipe2.name))
+            .map(|(recipe, count)| {
+                json!({
+                    "id": self.node_id(Node::Recipe(recipe, 0)),
+                    "name": recipe.name,
+                    "recipe": recipe.name,
+                    "count": count.to_f64(),
+                    "factory": {
+                        "name": recipe.factory_kind.building_name(),
+                        "type": format!("{:?}", recipe.factory_kind),
+                    },
+                })
+            })
+            .collect_vec();
+
+        format!("{:#}", json!({ "blocks": blocks }))
+    }
+
+    /// Export a plan in the Prometheus text exposition format, so it can be scraped into a
+    /// dashboard. Emits one `factory_recipe_machines` gauge per recipe of `machine_counts` and one
+    /// `factory_item_rate` gauge (items per second) per item of `rates`, both ordered by name:
+    /// ```text
+    /// factory_recipe_machines{recipe="iron-plate",kind="Smelter"} 4
+    /// factory_item_rate{item="iron-plate"} 45
+    /// ```
+    pub fn to_prometheus(
+        &self,
+        machine_counts: &HashMap<&Recipe, Decimal>,
+        rates: &HashMap<&Item, Decimal>,
+    ) -> String {
+        let machines = machine_counts
+            .iter()
+            .sorted_by(|(recipe1, _), (recipe2, _)| recipe1.name.cmp(&recipe2.name))
+            .map(|(recipe, count)| {
+                format!(
+                    "factory_recipe_machines{{recipe=\"{}\",kind=\"{:?}\"}} {}",
+                    escape_label_value(&recipe.name),
+                    recipe.factory_kind,
+                    count.normalize()
+                )
+            });
+        let item_rates = rates
+            .iter()
+            .sorted_by(|(item1, _), (item2, _)| item1.name.cmp(&item2.name))
+            .map(|(item, rate)| {
+                format!(
+                    "factory_item_rate{{item=\"{}\"}} {}",
+                    escape_label_value(&item.name),
+                    rate.normalize()
+                )
+            });
+
+        [
+            "# HELP factory_recipe_machines Buildings crafting the recipe.".to_string(),
+            "# TYPE factory_recipe_machines gauge".to_string(),
+        ]
+        .into_iter()
+        .chain(machines)
+        .chain([
+            "# HELP factory_item_rate Items per second flowing through the factory.".to_string(),
+            "# TYPE factory_item_rate gauge".to_string(),
+        ])
+        .chain(item_rates)
+        .map(|line| line + "\n")
+        .collect()
+    }
+
+    /// Render the graph to an SVG file with Graphviz `dot`. A warning is printed to stderr for a
+    /// graph which [`Self::is_degenerate`], but it's rendered anyway.
+    /// If `dot` isn't on PATH, the common install locations from [`DEFAULT_DOT_LOCATIONS`] are
+    /// tried before failing with [`FactoryError::GraphvizNotFound`].
+    pub fn save_as_svg(&self, file_name: impl AsRef<Path>) -> FactoryResult<()> {
+        self.save_as_svg_with_dot_locations(file_name, DEFAULT_DOT_LOCATIONS)
+    }
+
+    /// Same as [`Self::save_as_svg`], but with a custom list of locations probed for the `dot`
+    /// binary when it isn't on PATH.
+    pub fn save_as_svg_with_dot_locations(
+        &self,
+        file_name: impl AsRef<Path>,
+        dot_locations: &[&str],
+    ) -> FactoryResult<()> {
+        if self.is_degenerate() {
+            eprintln!(
+                "Rendering a graph with {} nodes and {} edges, was it filtered too much?",
+                self.data.node_count(),
+                self.data.edge_count()
+            );
+        }
+
+        let dot = self.to_dot();
+        let mut cmd = spawn_dot(dot_locations)?;
+
+        {
+            let mut stdin = cmd.stdin.take().ok_or(FactoryError::CommandSpawn(
+                "Failed to take stdin".to_string(),
+            ))?;
+
+            stdin.write_all(dot.as_bytes())?;
+        }
+
+        let output = cmd.wait_with_output()?;
+
+        if !output.stderr.is_empty() {
+            println!("stderr: {}", std::str::from_utf8(&output.stderr)?);
+        }
+
+        let mut file = fs::File::create(file_name)?;
+        file.write_all(&output.stdout)?;
+
+        Ok(())
+    }
+}
+
+/// Fill colors of recipe nodes in [`CraftingGraph::to_dot_styled`].
+const FACTORY_KIND_COLORS: [(FactoryKind, &str); 9] = [
+    (FactoryKind::Assembler, "lightblue"),
+    (FactoryKind::OilRefinery, "sienna"),
+    (FactoryKind::ChemicalPlant, "palegreen"),
+    (FactoryKind::Centrifuge, "yellowgreen"),
+    (FactoryKind::Smelter, "orange"),
+    (FactoryKind::RocketSilo, "lightgray"),
+    (FactoryKind::MiningDrill, "khaki"),
+    (FactoryKind::BurnerSmelter, "sandybrown"),
+    (FactoryKind::BurnerMiningDrill, "darkkhaki"),
+];
+
+/// Get the items whose tier differs between two graphs, keyed by item name, with their tier
+/// `before` and `after`. Items present in only one of the graphs have `None` on the other side.
+/// Recipes are left out, as they commonly share the name of the item they produce.
+pub fn tier_diff(
+    before: &CraftingGraph,
+    after: &CraftingGraph,
+) -> HashMap<String, (Option<Tier>, Option<Tier>)> {
+    let item_names = |graph: &CraftingGraph| {
+        graph
+            .iter_nodes()
+            .filter_map(|node| match node {
+                Node::Item(item, _) => Some(item.name.clone()),
+                Node::Recipe(..) => None,
+            })
+            .collect_vec()
+    };
+
+    item_names(before)
+        .into_iter()
+        .chain(item_names(after))
+        .unique()
+        .map(|name| {
+            let tiers = (before.item_tier(&name), after.item_tier(&name));
+            (name, tiers)
+        })
+        .filter(|(_, (before, after))| before != after)
+        .collect()
+}
+
+fn factory_kind_color(kind: &FactoryKind) -> &'static str {
+    FACTORY_KIND_COLORS
+        .iter()
+        .find_map(|(known_kind, color)| (known_kind == kind).then_some(*color))
+        .unwrap_or("white")
+}
+
+/// Format a crafting time as seconds with one decimal, like `3.2s`.
+pub fn format_duration(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+/// Escape a Prometheus label value, which is written between double quotes.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Locations where Graphviz is commonly installed, probed when `dot` isn't on PATH.
+pub const DEFAULT_DOT_LOCATIONS: &[&str] = &[
+    "/usr/bin/dot",
+    "/usr/local/bin/dot",
+    "/opt/homebrew/bin/dot",
+    r"C:\Program Files\Graphviz\bin\dot.exe",
+];
+
+fn spawn_dot(dot_locations: &[&str]) -> FactoryResult<Child> {
+    let spawn = |program: &str| {
+        Command::new(program)
+            .arg("-Tsvg")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    };
+
+    match spawn("dot") {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            for location in dot_locations {
+                if let Ok(child) = spawn(location) {
+                    println!("Graphviz not found on PATH, using {location}");
+                    return Ok(child);
+                }
+            }
+
+            Err(FactoryError::GraphvizNotFound(
+                dot_locations.iter().map(ToString::to_string).collect(),
+            ))
+        }
+        other => Ok(other?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        ops::ControlFlow,
+        time::Duration,
+    };
+
+    use itertools::Itertools;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        entities::{FactoryKind, Item, Recipe},
+        error::FactoryError,
+        traits::{self, DataSource},
+    };
+
+    use petgraph::{graph::NodeIndex, Direction};
+
+    use super::{CraftingGraph, EdgeInfo, FlowDirection, GraphOptions, Node, Tier, TreeOptions};
+
+    pub(super) struct DataSetMock {
+        pub(super) items: Vec<Item>,
+        pub(super) recipes: Vec<Recipe>,
+    }
+
+    impl traits::DataSource for DataSetMock {
+        fn from_str(
+            _recipes_str: &str,
+            _natural_item_names: &[String],
+        ) -> crate::error::FactoryResult<Self>
+        where
+            Self: std::marker::Sized,
+        {
+            Ok(Self::new())
+        }
 
         fn iter_items(&self) -> impl Iterator<Item = &Item> {
             self.items.iter()
         }
 
-        fn iter_recipes(&self) -> impl Iterator<Item = &Recipe> {
-            self.recipes.iter()
+        fn iter_recipes(&self) -> impl Iterator<Item = &Recipe> {
+            self.recipes.iter()
+        }
+    }
+
+    impl DataSetMock {
+        pub(super) fn new() -> Self {
+            let natural_items = ["iron-ore", "copper-ore"].into_iter().map(|name| Item {
+                name: name.to_string(),
+                natural: true,
+            });
+
+            let other_items = [
+                "iron-plate",
+                "copper-plate",
+                "copper-cable",
+                "electronic-circuit",
+            ]
+            .into_iter()
+            .map(|name| Item {
+                name: name.to_string(),
+                natural: false,
+            });
+
+            let items = natural_items.chain(other_items).collect_vec();
+
+            let item = |name: &str| -> Item {
+                items
+                    .iter()
+                    .find(|item| item.name == name)
+                    .unwrap_or_else(|| panic!("Item not found: {name}"))
+                    .clone()
+            };
+
+            let recipe = |name: &str,
+                          time: f64,
+                          inputs: &[(Decimal, Item)],
+                          outputs: &[(Decimal, Item)],
+                          kind: FactoryKind| {
+                Recipe {
+                    name: name.to_string(),
+                    results: outputs.to_vec(),
+                    catalysts: vec![],
+                    ingredients: inputs.to_vec(),
+                    time: Duration::from_secs_f64(time),
+                    category: "crafting".to_string(),
+                    factory_kind: kind,
+                    allows_productivity: false,
+                    reversible: false,
+                    version: None,
+                }
+            };
+
+            let recipes = vec![
+                recipe(
+                    "copper-plate",
+                    3.2,
+                    &[(dec!(1), item("copper-ore"))],
+                    &[(dec!(1), item("copper-plate"))],
+                    FactoryKind::Assembler,
+                ),
+                recipe(
+                    "copper-cable",
+                    0.5,
+                    &[(dec!(1), item("copper-plate"))],
+                    &[(dec!(2), item("copper-cable"))],
+                    FactoryKind::Assembler,
+                ),
+                recipe(
+                    "iron-plate",
+                    3.2,
+                    &[(dec!(1), item("iron-ore"))],
+                    &[(dec!(1), item("iron-plate"))],
+                    FactoryKind::Assembler,
+                ),
+                recipe(
+                    "electronic-circuit",
+                    0.5,
+                    &[
+                        (dec!(3), item("copper-cable")),
+                        (dec!(1), item("iron-plate")),
+                    ],
+                    &[(dec!(1), item("electronic-circuit"))],
+                    FactoryKind::Assembler,
+                ),
+            ];
+
+            Self { recipes, items }
+        }
+
+        /// Build a mock from `(name, ingredients, results)` descriptions. Every recipe takes 1s
+        /// in an assembler and items listed in `naturals` are marked as natural.
+        pub(super) fn from_recipes(naturals: &[&str], recipes: &[MockRecipe]) -> Self {
+            let item = |name: &str| Item {
+                name: name.to_string(),
+                natural: naturals.contains(&name),
+            };
+
+            let recipes = recipes
+                .iter()
+                .map(|(name, ingredients, results)| Recipe {
+                    name: name.to_string(),
+                    results: results
+                        .iter()
+                        .map(|(amount, name)| (*amount, item(name)))
+                        .collect(),
+                    catalysts: vec![],
+                    ingredients: ingredients
+                        .iter()
+                        .map(|(amount, name)| (*amount, item(name)))
+                        .collect(),
+                    time: Duration::from_secs(1),
+                    category: "crafting".to_string(),
+                    factory_kind: FactoryKind::Assembler,
+                    allows_productivity: false,
+                    reversible: false,
+                    version: None,
+                })
+                .collect_vec();
+
+            let items = recipes
+                .iter()
+                .flat_map(|recipe| recipe.ingredients.iter().chain(recipe.results.iter()))
+                .map(|(_, item)| item.clone())
+                .unique()
+                .collect();
+
+            Self { recipes, items }
+        }
+    }
+
+    pub(super) type MockRecipe<'a> = (&'a str, &'a [(Decimal, &'a str)], &'a [(Decimal, &'a str)]);
+
+    #[test]
+    fn test_factory_kind_for() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(
+            graph.factory_kind_for("copper-cable"),
+            Some(data.get_recipe("copper-cable").factory_kind.clone())
+        );
+        assert_eq!(graph.factory_kind_for("copper-ore"), None);
+        assert_eq!(graph.factory_kind_for("unknown"), None);
+    }
+
+    #[test]
+    fn test_tier_diff() {
+        let before_data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+            ],
+        );
+        let after_data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                (
+                    "iron-stick",
+                    &[(dec!(1), "iron-plate")],
+                    &[(dec!(2), "iron-stick")],
+                ),
+            ],
+        );
+        let before = CraftingGraph::from_dataset(&before_data);
+        let after = CraftingGraph::from_dataset(&after_data);
+
+        assert_eq!(before.item_tier("iron-gear-wheel"), Some(4));
+        assert_eq!(before.recipe_tier("iron-gear-wheel"), Some(3));
+        assert_eq!(before.item_tier("iron-stick"), None);
+
+        assert_eq!(
+            super::tier_diff(&before, &after),
+            HashMap::from([
+                ("iron-gear-wheel".to_string(), (Some(4), Some(2))),
+                ("iron-stick".to_string(), (None, Some(4))),
+            ])
+        );
+        assert!(super::tier_diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_focus() {
+        let mut data = DataSetMock::new();
+        data.recipes.push(Recipe {
+            name: "iron-gear-wheel".to_string(),
+            results: vec![(
+                dec!(1),
+                Item {
+                    name: "iron-gear-wheel".to_string(),
+                    natural: false,
+                },
+            )],
+            ingredients: vec![(dec!(2), data.get_item("iron-plate").clone())],
+            ..data.get_recipe("iron-plate").clone()
+        });
+        let graph = CraftingGraph::from_dataset(&data);
+        let names = |graph: &CraftingGraph| {
+            graph
+                .iter_nodes()
+                .map(|node| node.to_string())
+                .sorted()
+                .collect_vec()
+        };
+
+        let focused = graph.focus(&["electronic-circuit"]);
+        let full_closure = graph
+            .iter_nodes()
+            .filter(|node| node.name() != "iron-gear-wheel")
+            .map(|node| node.to_string())
+            .sorted()
+            .collect_vec();
+
+        assert_eq!(names(&focused), full_closure);
+        assert_eq!(focused.data.edge_count(), 9);
+        assert_eq!(focused.natural_items.len(), 2);
+        assert_eq!(
+            names(&graph.subgraph_for("electronic-circuit").unwrap()),
+            names(&focused)
+        );
+
+        let iron_only = graph.focus(&["iron-gear-wheel", "unknown"]);
+        assert_eq!(iron_only.data.node_count(), 5);
+        assert_eq!(iron_only.natural_items, vec![data.get_item("iron-ore")]);
+        assert_eq!(
+            names(&graph.focus(&["electronic-circuit", "iron-gear-wheel"])),
+            names(&graph)
+        );
+        assert!(graph.subgraph_for("unknown").is_none());
+    }
+
+    #[test]
+    fn test_components() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore", "stone"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                (
+                    "stone-brick",
+                    &[(dec!(2), "stone")],
+                    &[(dec!(1), "stone-brick")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let components = graph.components();
+
+        assert_eq!(components.len(), 2);
+        let iron = components
+            .iter()
+            .find(|component| {
+                component
+                    .iter_nodes()
+                    .contains(&graph.get_item_node("iron-gear-wheel"))
+            })
+            .unwrap();
+        let stone = components
+            .iter()
+            .find(|component| {
+                component
+                    .iter_nodes()
+                    .contains(&graph.get_item_node("stone-brick"))
+            })
+            .unwrap();
+
+        assert_eq!(iron.data.node_count(), 5);
+        assert_eq!(iron.data.edge_count(), 4);
+        assert_eq!(iron.natural_items, vec![data.get_item("iron-ore")]);
+        assert!(!iron.iter_nodes().contains(&graph.get_item_node("stone")));
+        assert_eq!(stone.data.node_count(), 3);
+        assert_eq!(stone.natural_items, vec![data.get_item("stone")]);
+
+        for component in &components {
+            for node in component.iter_nodes() {
+                assert!(graph.iter_nodes().contains(&node));
+            }
+        }
+    }
+
+    #[test]
+    fn test_independent_recipe_groups() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore", "stone"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                (
+                    "stone-brick",
+                    &[(dec!(2), "stone")],
+                    &[(dec!(1), "stone-brick")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let groups = graph
+            .independent_recipe_groups()
+            .into_iter()
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(|recipe| recipe.name.as_str())
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        assert_eq!(
+            groups,
+            vec![vec!["iron-gear-wheel", "iron-plate"], vec!["stone-brick"]]
+        );
+    }
+
+    #[test]
+    fn test_crafting_graph_structure() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        assert_eq!(
+            graph.iter_nodes().count(),
+            data.recipes.len() + data.items.len()
+        );
+
+        for item in data.iter_items() {
+            assert_eq!(data.try_get_item(&item.name), Some(item));
+        }
+
+        for recipe in data.iter_recipes() {
+            assert_eq!(data.try_get_recipe(&recipe.name), Some(recipe));
         }
     }
 
-    impl DataSetMock {
-        fn new() -> Self {
-            let natural_items = ["iron-ore", "copper-ore"].into_iter().map(|name| Item {
-                name: name.to_string(),
-                natural: true,
-            });
+    #[test]
+    fn test_from_parts() {
+        let data = DataSetMock::new();
+        let iron_ore = data.get_item("iron-ore");
+        let iron_plate = data.get_item("iron-plate");
+        let smelting = data.get_recipe("iron-plate");
+
+        let graph = CraftingGraph::from_parts(
+            vec![
+                Node::Item(iron_ore, 0),
+                Node::Recipe(smelting, 1),
+                Node::Item(iron_plate, 2),
+            ],
+            vec![(0, 1, dec!(1)), (1, 2, dec!(1))],
+            vec![iron_ore],
+        )
+        .expect("All edges are in range");
+
+        assert_eq!(graph.data.node_count(), 3);
+        assert_eq!(
+            graph.get_results_for_recipe_idxs(Node::Recipe(smelting, 1)),
+            Some(vec![graph.get_item_idx_from_name("iron-plate").unwrap()])
+        );
+
+        let out_of_range =
+            CraftingGraph::from_parts(vec![Node::Item(iron_ore, 0)], vec![(0, 1, dec!(1))], vec![]);
+
+        assert!(matches!(
+            out_of_range,
+            Err(FactoryError::EdgeEndpointOutOfRange {
+                from: 0,
+                to: 1,
+                node_count: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_node_display() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(super::format_duration(Duration::from_millis(3200)), "3.2s");
+        assert_eq!(super::format_duration(Duration::from_secs(1)), "1.0s");
+        assert_eq!(
+            graph.get_item_node("iron-plate").to_string(),
+            format!("iron-plate [{}]", graph.item_tier("iron-plate").unwrap())
+        );
+        assert_eq!(
+            graph.get_recipe_node("iron-plate").to_string(),
+            format!(
+                "iron-plate ({}) [{}]",
+                super::format_duration(data.get_recipe("iron-plate").time),
+                graph.recipe_tier("iron-plate").unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_recipes_requiring() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore", "copper-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "copper-plate",
+                    &[(dec!(1), "copper-ore")],
+                    &[(dec!(1), "copper-plate")],
+                ),
+                (
+                    "copper-cable",
+                    &[(dec!(1), "copper-plate")],
+                    &[(dec!(2), "copper-cable")],
+                ),
+                (
+                    "electronic-circuit",
+                    &[(dec!(1), "iron-plate"), (dec!(3), "copper-cable")],
+                    &[(dec!(1), "electronic-circuit")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                (
+                    "iron-gear-wheel-from-scrap",
+                    &[(dec!(1), "copper-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                (
+                    "inserter",
+                    &[
+                        (dec!(1), "electronic-circuit"),
+                        (dec!(1), "iron-gear-wheel"),
+                    ],
+                    &[(dec!(1), "inserter")],
+                ),
+                (
+                    "transport-belt",
+                    &[(dec!(1), "iron-gear-wheel")],
+                    &[(dec!(2), "transport-belt")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let names = |recipes: Vec<&Recipe>| {
+            recipes
+                .into_iter()
+                .map(|recipe| recipe.name.clone())
+                .collect_vec()
+        };
+
+        assert_eq!(
+            names(graph.recipes_requiring("iron-plate")),
+            vec!["electronic-circuit", "inserter", "iron-gear-wheel"]
+        );
+        assert_eq!(
+            names(graph.recipes_requiring("copper-plate")),
+            vec![
+                "copper-cable",
+                "electronic-circuit",
+                "inserter",
+                "iron-gear-wheel-from-scrap"
+            ]
+        );
+        assert!(graph.recipes_requiring("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_explain_tier() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(
+            graph.explain_tier("electronic-circuit").unwrap(),
+            [
+                "electronic-circuit: tier 6, one past its lowest-tier recipe",
+                "  electronic-circuit: tier 5, one past its deepest ingredient",
+                "    copper-cable: tier 4",
+                "    iron-plate: tier 2",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            graph.explain_tier("iron-ore").unwrap(),
+            "iron-ore: tier 0, natural items start at 0"
+        );
+        assert_eq!(graph.explain_tier("unknown"), None);
+    }
+
+    #[test]
+    fn test_is_craftable() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let circuit = graph.get_item_node("electronic-circuit");
+        let all: HashSet<String> = data
+            .recipes
+            .iter()
+            .map(|recipe| recipe.name.clone())
+            .collect();
+
+        assert!(graph.is_craftable(circuit, &all));
+        assert!(graph.is_craftable(graph.get_item_node("iron-ore"), &HashSet::new()));
+
+        let mut without_cable = all.clone();
+        without_cable.remove("copper-cable");
+        assert!(!graph.is_craftable(circuit, &without_cable));
+        assert!(!graph.is_craftable(graph.get_recipe_node("electronic-circuit"), &without_cable));
+        assert!(graph.is_craftable(graph.get_item_node("copper-plate"), &without_cable));
+    }
+
+    #[test]
+    fn test_recipes_to_unlock() {
+        let data = DataSetMock::new();
+        let mut graph = CraftingGraph::from_dataset(&data);
+        let circuit = graph.get_item_node("electronic-circuit");
+        let names = |recipes: HashSet<&Recipe>| -> HashSet<String> {
+            recipes
+                .into_iter()
+                .map(|recipe| recipe.name.clone())
+                .collect()
+        };
+        let unlocked: HashSet<String> = ["copper-plate", "iron-plate"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        assert_eq!(
+            names(graph.recipes_to_unlock(circuit, &unlocked).unwrap()),
+            HashSet::from(["copper-cable".to_string(), "electronic-circuit".to_string()])
+        );
+        assert_eq!(
+            names(
+                graph
+                    .recipes_to_unlock(graph.get_recipe_node("copper-cable"), &unlocked)
+                    .unwrap()
+            ),
+            HashSet::from(["copper-cable".to_string()])
+        );
+        assert!(graph
+            .recipes_to_unlock(graph.get_item_node("iron-plate"), &unlocked)
+            .unwrap()
+            .is_empty());
+
+        graph.remove_recipe("copper-cable");
+        assert!(graph.recipes_to_unlock(circuit, &unlocked).is_none());
+    }
+
+    #[test]
+    fn test_forced_recipes() {
+        let mut data = DataSetMock::new();
+        let mut cable_from_ore = data.get_recipe("copper-cable").clone();
+        cable_from_ore.name = "copper-cable-from-ore".to_string();
+        cable_from_ore.ingredients = vec![(dec!(1), data.get_item("copper-ore").clone())];
+        data.recipes.push(cable_from_ore);
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let forced = graph
+            .forced_recipes()
+            .into_iter()
+            .map(|(item, recipe)| (item.name.clone(), recipe.name.clone()))
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(
+            forced,
+            HashMap::from([
+                ("iron-plate".to_string(), "iron-plate".to_string()),
+                ("copper-plate".to_string(), "copper-plate".to_string()),
+                (
+                    "electronic-circuit".to_string(),
+                    "electronic-circuit".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_redundant_for_reachability() {
+        let mut data = DataSetMock::new();
+        let mut cable_from_ore = data.get_recipe("copper-cable").clone();
+        cable_from_ore.name = "copper-cable-from-ore".to_string();
+        cable_from_ore.ingredients = vec![(dec!(1), data.get_item("copper-ore").clone())];
+        data.recipes.push(cable_from_ore);
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let redundant = graph
+            .redundant_for_reachability()
+            .into_iter()
+            .map(|recipe| recipe.name.as_str())
+            .collect_vec();
+
+        assert_eq!(redundant, vec!["copper-cable", "copper-cable-from-ore"]);
+    }
+
+    #[test]
+    fn test_validate_edges() {
+        let data = DataSetMock::new();
+        let iron_ore = data.get_item("iron-ore");
+        let iron_plate = data.get_item("iron-plate");
+        let smelting = data.get_recipe("iron-plate");
+        let nodes = vec![
+            Node::Item(iron_ore, 0),
+            Node::Recipe(smelting, 1),
+            Node::Item(iron_plate, 2),
+        ];
+
+        let graph = CraftingGraph::from_dataset(&data);
+        assert!(graph.validate_edges().is_ok());
+
+        let zero_weight = CraftingGraph::from_parts(
+            nodes,
+            vec![(0, 1, dec!(0)), (1, 2, dec!(1))],
+            vec![iron_ore],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            zero_weight.validate_edges(),
+            Err(FactoryError::NonPositiveAmount { recipe, item })
+                if recipe == "iron-plate" && item == "iron-ore"
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_and_restore_tiers() {
+        let data = DataSetMock::new();
+        let iron_ore = data.get_item("iron-ore");
+        let iron_plate = data.get_item("iron-plate");
+        let smelting = data.get_recipe("iron-plate");
+
+        let mut graph = CraftingGraph::from_parts(
+            vec![
+                Node::Item(iron_ore, 0),
+                Node::Recipe(smelting, 5),
+                Node::Item(iron_plate, 7),
+            ],
+            vec![(0, 1, dec!(1)), (1, 2, dec!(1))],
+            vec![iron_ore],
+        )
+        .expect("All edges are in range");
+
+        let snapshot = graph.snapshot_tiers();
+        graph.adjust_tiers();
+        assert_ne!(graph.snapshot_tiers(), snapshot);
+
+        graph.restore_tiers(snapshot.clone()).unwrap();
+        assert_eq!(graph.snapshot_tiers(), snapshot);
+
+        graph.adjust_tiers();
+        let adjusted = graph.snapshot_tiers();
+        let result = graph.restore_tiers(vec![(NodeIndex::new(0), 0), (NodeIndex::new(3), 0)]);
+        assert!(matches!(
+            result,
+            Err(FactoryError::NodeIndexOutOfRange {
+                index: 3,
+                node_count: 3
+            })
+        ));
+        assert_eq!(graph.snapshot_tiers(), adjusted);
+    }
+
+    #[test]
+    fn test_dedup_item_nodes() {
+        let data = DataSetMock::new();
+        let iron_ore = data.get_item("iron-ore");
+        let iron_plate = data.get_item("iron-plate");
+        let smelting = data.get_recipe("iron-plate");
+        let circuit = data.get_recipe("electronic-circuit");
+
+        let mut graph = CraftingGraph::from_parts(
+            vec![
+                Node::Item(iron_ore, 0),
+                Node::Recipe(smelting, 1),
+                Node::Item(iron_plate, 2),
+                Node::Item(iron_plate, 2),
+                Node::Recipe(circuit, 3),
+            ],
+            vec![
+                (0, 1, dec!(1)),
+                (1, 2, dec!(1)),
+                (1, 3, dec!(1)),
+                (3, 4, dec!(1)),
+            ],
+            vec![iron_ore],
+        )
+        .expect("All edges are in range");
+
+        graph.dedup_item_nodes();
+
+        assert_eq!(graph.data.node_count(), 4);
+        assert_eq!(graph.data.edge_count(), 3);
+        let plate_idx = graph.get_item_idx_from_name("iron-plate").unwrap();
+        let circuit_idx = graph
+            .get_recipe_idx_from_name("electronic-circuit")
+            .unwrap();
+        let smelting_idx = graph.get_recipe_idx_from_name("iron-plate").unwrap();
+        assert!(graph.data.contains_edge(smelting_idx, plate_idx));
+        assert!(graph.data.contains_edge(plate_idx, circuit_idx));
+    }
+
+    #[test]
+    fn test_to_helmod_json() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let machine_counts = [
+            (data.get_recipe("iron-plate"), dec!(4)),
+            (data.get_recipe("copper-cable"), dec!(1.5)),
+        ]
+        .into_iter()
+        .collect();
+
+        let exported: serde_json::Value =
+            serde_json::from_str(&graph.to_helmod_json(&machine_counts)).unwrap();
+
+        assert_eq!(
+            exported,
+            serde_json::json!({
+                "blocks": [
+                    {
+                        "id": "recipe:copper-cable",
+                        "name": "copper-cable",
+                        "recipe": "copper-cable",
+                        "count": 1.5,
+                        "factory": {"name": "assembling-machine-2", "type": "Assembler"},
+                    },
+                    {
+                        "id": "recipe:iron-plate",
+                        "name": "iron-plate",
+                        "recipe": "iron-plate",
+                        "count": 4.0,
+                        "factory": {"name": "assembling-machine-2", "type": "Assembler"},
+                    },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_prometheus() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let machine_counts = HashMap::from([
+            (data.get_recipe("iron-plate"), dec!(4)),
+            (data.get_recipe("copper-cable"), dec!(1.50)),
+        ]);
+        let rates = HashMap::from([(data.get_item("iron-plate"), dec!(45))]);
+
+        assert_eq!(
+            graph.to_prometheus(&machine_counts, &rates),
+            [
+                "# HELP factory_recipe_machines Buildings crafting the recipe.",
+                "# TYPE factory_recipe_machines gauge",
+                r#"factory_recipe_machines{recipe="copper-cable",kind="Assembler"} 1.5"#,
+                r#"factory_recipe_machines{recipe="iron-plate",kind="Assembler"} 4"#,
+                "# HELP factory_item_rate Items per second flowing through the factory.",
+                "# TYPE factory_item_rate gauge",
+                r#"factory_item_rate{item="iron-plate"} 45"#,
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            super::escape_label_value("say \"hi\"\\\n"),
+            r#"say \"hi\"\\\n"#
+        );
+    }
+
+    #[test]
+    fn test_shared_dependency() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(
+            graph.shared_dependency("electronic-circuit", "copper-cable"),
+            Some(data.get_recipe("copper-cable"))
+        );
+        assert_eq!(graph.shared_dependency("iron-plate", "copper-cable"), None);
+    }
+
+    #[test]
+    fn test_upstream_recipes() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "water", "coal"],
+            &[
+                (
+                    "basic-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(45), "petroleum-gas")],
+                ),
+                (
+                    "advanced-oil-processing",
+                    &[(dec!(100), "crude-oil"), (dec!(50), "water")],
+                    &[(dec!(55), "petroleum-gas")],
+                ),
+                (
+                    "plastic-bar",
+                    &[(dec!(20), "petroleum-gas"), (dec!(1), "coal")],
+                    &[(dec!(2), "plastic-bar")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let upstream = graph
+            .upstream_recipes("plastic-bar")
+            .unwrap()
+            .into_iter()
+            .map(|recipe| recipe.name.as_str())
+            .sorted()
+            .collect_vec();
+        assert_eq!(
+            upstream,
+            vec!["advanced-oil-processing", "basic-oil-processing"]
+        );
+        assert_eq!(
+            graph.upstream_recipes("basic-oil-processing"),
+            Some(HashSet::new())
+        );
+        assert_eq!(graph.upstream_recipes("rocket-fuel"), None);
+    }
+
+    #[test]
+    fn test_bottleneck_items() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(graph.bottleneck_items(), vec![data.get_item("iron-plate")]);
+
+        // A chain far deeper than a recursive search could go without overflowing the stack.
+        let (plate, smelting) = (data.get_item("iron-plate"), data.get_recipe("iron-plate"));
+        let node_count = 200_001;
+        let chain = CraftingGraph::from_parts(
+            (0..node_count)
+                .map(|idx| match idx % 2 {
+                    0 => Node::Item(plate, idx),
+                    _ => Node::Recipe(smelting, idx),
+                })
+                .collect(),
+            (1..node_count).map(|idx| (idx - 1, idx, dec!(1))).collect(),
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(chain.articulation_points().len(), node_count - 2);
+    }
+
+    #[test]
+    fn test_crafting_trees_with_self_loops() {
+        let data = DataSetMock::from_recipes(
+            &["uranium-ore"],
+            &[
+                (
+                    "uranium-processing",
+                    &[(dec!(10), "uranium-ore")],
+                    &[(dec!(1), "uranium-235"), (dec!(9), "uranium-238")],
+                ),
+                (
+                    "kovarex-enrichment-process",
+                    &[(dec!(40), "uranium-235"), (dec!(5), "uranium-238")],
+                    &[(dec!(41), "uranium-235"), (dec!(2), "uranium-238")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let target = graph.get_item_node("uranium-235");
+        let kovarex_tree = |options| {
+            graph
+                .get_crafting_trees_with_options(target, 10, options)
+                .unwrap()
+                .into_iter()
+                .find(|tree| {
+                    tree.iter_nodes().any(|node| {
+                        matches!(node, Node::Recipe(recipe, _) if recipe.name == "kovarex-enrichment-process")
+                    })
+                })
+                .unwrap()
+        };
+        let uranium_235_count = |tree: &CraftingGraph| {
+            tree.iter_nodes()
+                .filter(|node| matches!(node, Node::Item(item, _) if item.name == "uranium-235"))
+                .count()
+        };
+
+        let acyclic = kovarex_tree(TreeOptions::default());
+        assert_eq!(uranium_235_count(&acyclic), 2);
+        assert!(!petgraph::algo::is_cyclic_directed(&acyclic.data));
+
+        let looped = kovarex_tree(TreeOptions {
+            allow_self_loops: true,
+            ..TreeOptions::default()
+        });
+        assert_eq!(uranium_235_count(&looped), 1);
+        assert!(petgraph::algo::is_cyclic_directed(&looped.data));
+    }
+
+    #[test]
+    fn test_crafting_trees_skip_byproducts() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "coal"],
+            &[
+                (
+                    "petroleum-gas",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(45), "petroleum-gas"), (dec!(10), "heavy-oil")],
+                ),
+                (
+                    "coal-liquefaction",
+                    &[(dec!(10), "coal")],
+                    &[(dec!(90), "heavy-oil")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let producers = |options| {
+            graph
+                .get_crafting_trees_with_options(graph.get_item_node("heavy-oil"), 10, options)
+                .unwrap()
+                .into_iter()
+                .flat_map(|tree| {
+                    tree.iter_nodes()
+                        .filter_map(|node| match node {
+                            Node::Recipe(recipe, _) => Some(recipe.name.clone()),
+                            Node::Item(..) => None,
+                        })
+                        .collect_vec()
+                })
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(producers(TreeOptions::default()), vec!["coal-liquefaction"]);
+        assert_eq!(
+            producers(TreeOptions {
+                allow_byproducts: true,
+                ..TreeOptions::default()
+            }),
+            vec!["coal-liquefaction", "petroleum-gas"]
+        );
+    }
+
+    #[test]
+    fn test_crafting_trees_with_frontier() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "coal"],
+            &[
+                (
+                    "basic-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(45), "petroleum-gas")],
+                ),
+                (
+                    "coal-gasification",
+                    &[(dec!(10), "coal")],
+                    &[(dec!(20), "petroleum-gas")],
+                ),
+                (
+                    "plastic-bar",
+                    &[(dec!(20), "petroleum-gas"), (dec!(1), "coal")],
+                    &[(dec!(2), "plastic-bar")],
+                ),
+                (
+                    "sulfur",
+                    &[(dec!(30), "petroleum-gas")],
+                    &[(dec!(2), "sulfur")],
+                ),
+                (
+                    "explosives",
+                    &[(dec!(1), "plastic-bar"), (dec!(1), "sulfur")],
+                    &[(dec!(2), "explosives")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let target = graph.get_item_node("explosives");
+
+        let unbounded = graph.get_crafting_trees(target, 10).unwrap();
+        assert_eq!(unbounded.len(), 4);
+
+        let capped = graph
+            .get_crafting_trees_with_frontier(target, 10, 1)
+            .unwrap();
+        assert!(!capped.is_empty());
+        assert!(capped.len() < unbounded.len());
+        let unbounded = unbounded
+            .iter()
+            .map(CraftingGraph::to_snapshot)
+            .collect_vec();
+        assert!(capped
+            .iter()
+            .all(|tree| unbounded.contains(&tree.to_snapshot())));
+    }
+
+    #[test]
+    fn test_for_each_crafting_tree() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "coal"],
+            &[
+                (
+                    "basic-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(45), "petroleum-gas")],
+                ),
+                (
+                    "coal-gasification",
+                    &[(dec!(10), "coal")],
+                    &[(dec!(20), "petroleum-gas")],
+                ),
+                (
+                    "plastic-bar",
+                    &[(dec!(20), "petroleum-gas"), (dec!(1), "coal")],
+                    &[(dec!(2), "plastic-bar")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let target = graph.get_item_node("plastic-bar");
+
+        let mut trees = vec![];
+        graph
+            .for_each_crafting_tree(target, |tree| {
+                trees.push(tree.clone());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(
+            graph
+                .get_crafting_trees(target, 10)
+                .unwrap()
+                .iter()
+                .map(CraftingGraph::to_snapshot)
+                .collect_vec(),
+            trees.iter().map(CraftingGraph::to_snapshot).collect_vec()
+        );
+
+        let options = TreeOptions {
+            max_frontier: Some(1),
+            ..TreeOptions::default()
+        };
+        let mut capped = vec![];
+        graph
+            .for_each_crafting_tree_with_options(target, options, |tree| {
+                capped.push(tree.to_snapshot());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(
+            capped,
+            graph
+                .get_crafting_trees_with_options(target, 10, options)
+                .unwrap()
+                .iter()
+                .map(CraftingGraph::to_snapshot)
+                .collect_vec()
+        );
+
+        let mut visited = 0;
+        graph
+            .for_each_crafting_tree(target, |_| {
+                visited += 1;
+                ControlFlow::Break(())
+            })
+            .unwrap();
+        assert_eq!(visited, 1);
+        assert_eq!(graph.get_crafting_trees(target, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_recipe_degree() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "water"],
+            &[
+                (
+                    "advanced-oil-processing",
+                    &[(dec!(100), "crude-oil"), (dec!(50), "water")],
+                    &[
+                        (dec!(25), "heavy-oil"),
+                        (dec!(45), "light-oil"),
+                        (dec!(55), "petroleum-gas"),
+                    ],
+                ),
+                (
+                    "heavy-oil-cracking",
+                    &[(dec!(40), "heavy-oil"), (dec!(30), "water")],
+                    &[(dec!(30), "light-oil")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(graph.recipe_degree("advanced-oil-processing"), Some((2, 3)));
+        assert_eq!(graph.recipe_degree("heavy-oil-cracking"), Some((2, 1)));
+        assert_eq!(graph.recipe_degree("coal-liquefaction"), None);
+        assert_eq!(graph.recipe_degree("heavy-oil"), None);
+    }
+
+    #[test]
+    fn test_edge_report() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
 
-            let other_items = [
-                "iron-plate",
-                "copper-plate",
-                "copper-cable",
-                "electronic-circuit",
+        let report = graph.edge_report();
+
+        let edge = |recipe: &str, item: &str, direction, amount| EdgeInfo {
+            recipe: recipe.to_string(),
+            item: item.to_string(),
+            direction,
+            amount,
+        };
+        assert_eq!(
+            report,
+            vec![
+                edge(
+                    "copper-cable",
+                    "copper-cable",
+                    FlowDirection::Output,
+                    dec!(2)
+                ),
+                edge(
+                    "copper-cable",
+                    "copper-plate",
+                    FlowDirection::Input,
+                    dec!(1)
+                ),
+                edge("copper-plate", "copper-ore", FlowDirection::Input, dec!(1)),
+                edge(
+                    "copper-plate",
+                    "copper-plate",
+                    FlowDirection::Output,
+                    dec!(1)
+                ),
+                edge(
+                    "electronic-circuit",
+                    "copper-cable",
+                    FlowDirection::Input,
+                    dec!(3)
+                ),
+                edge(
+                    "electronic-circuit",
+                    "electronic-circuit",
+                    FlowDirection::Output,
+                    dec!(1)
+                ),
+                edge(
+                    "electronic-circuit",
+                    "iron-plate",
+                    FlowDirection::Input,
+                    dec!(1)
+                ),
+                edge("iron-plate", "iron-ore", FlowDirection::Input, dec!(1)),
+                edge("iron-plate", "iron-plate", FlowDirection::Output, dec!(1)),
             ]
+        );
+        assert_eq!(report.len(), graph.data.edge_count());
+    }
+
+    #[test]
+    fn test_raw_resource_types() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "coal", "water", "iron-ore"],
+            &[
+                (
+                    "basic-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(45), "petroleum-gas")],
+                ),
+                (
+                    "coal-gasification",
+                    &[(dec!(10), "coal"), (dec!(50), "water")],
+                    &[(dec!(20), "petroleum-gas")],
+                ),
+                (
+                    "plastic-bar",
+                    &[(dec!(20), "petroleum-gas"), (dec!(1), "coal")],
+                    &[(dec!(2), "plastic-bar")],
+                ),
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let names = |items: HashSet<&Item>| {
+            items
+                .into_iter()
+                .map(|item| item.name.clone())
+                .sorted()
+                .collect_vec()
+        };
+
+        // Both ways of making petroleum gas count, and coal is needed along either of them.
+        let plastic = graph
+            .raw_resource_types(graph.get_item_node("plastic-bar"))
+            .unwrap();
+        assert_eq!(names(plastic), vec!["coal", "crude-oil", "water"]);
+
+        let gasification = graph
+            .raw_resource_types(graph.get_recipe_node("coal-gasification"))
+            .unwrap();
+        assert_eq!(names(gasification), vec!["coal", "water"]);
+
+        let ore = graph
+            .raw_resource_types(graph.get_item_node("iron-ore"))
+            .unwrap();
+        assert_eq!(names(ore), vec!["iron-ore"]);
+
+        let unknown = Recipe {
+            name: "unknown".to_string(),
+            ..data.get_recipe("iron-plate").clone()
+        };
+        assert!(graph
+            .raw_resource_types(Node::Recipe(&unknown, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_items_by_consumer_count() {
+        let data = DataSetMock::from_recipes(
+            &["iron-plate"],
+            &[
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                ("pipe", &[(dec!(1), "iron-plate")], &[(dec!(1), "pipe")]),
+                (
+                    "engine-unit",
+                    &[(dec!(1), "iron-gear-wheel"), (dec!(2), "pipe")],
+                    &[(dec!(1), "engine-unit")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let ranking = graph
+            .items_by_consumer_count()
             .into_iter()
-            .map(|name| Item {
-                name: name.to_string(),
-                natural: false,
-            });
+            .map(|(item, count)| (item.name.as_str(), count))
+            .collect_vec();
+        assert_eq!(
+            ranking,
+            vec![
+                ("iron-plate", 2),
+                ("iron-gear-wheel", 1),
+                ("pipe", 1),
+                ("engine-unit", 0)
+            ]
+        );
+    }
 
-            let items = natural_items.chain(other_items).collect_vec();
+    #[test]
+    fn test_production_depth() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
 
-            let item = |name: &str| -> Item {
-                items
-                    .iter()
-                    .find(|item| item.name == name)
-                    .unwrap_or_else(|| panic!("Item not found: {name}"))
-                    .clone()
-            };
+        // copper-ore -> copper-plate -> copper-cable -> electronic-circuit
+        assert_eq!(graph.production_depth(), 3);
+    }
 
-            let recipe = |name: &str,
-                          time: f64,
-                          inputs: &[(Decimal, Item)],
-                          outputs: &[(Decimal, Item)],
-                          kind: FactoryKind| {
-                Recipe {
-                    name: name.to_string(),
-                    results: outputs.to_vec(),
-                    ingredients: inputs.to_vec(),
-                    time: Duration::from_secs_f64(time),
-                    factory_kind: kind,
-                }
-            };
+    #[test]
+    fn test_recipe_depth() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
 
-            let recipes = vec![
-                recipe(
-                    "copper-plate",
-                    3.2,
-                    &[(dec!(1), item("copper-ore"))],
-                    &[(dec!(1), item("copper-plate"))],
-                    FactoryKind::Assembler,
+        assert_eq!(graph.recipe_depth("electronic-circuit"), Some(3));
+        assert_eq!(graph.recipe_tier("electronic-circuit"), Some(5));
+        assert_eq!(graph.recipe_depth("copper-cable"), Some(2));
+        assert_eq!(graph.recipe_depth("iron-plate"), Some(1));
+        assert_eq!(graph.recipe_depth("nonexistent"), None);
+    }
+
+    #[test]
+    fn test_to_snapshot() {
+        let mut data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let snapshot = graph.to_snapshot();
+
+        assert_eq!(
+            snapshot,
+            [
+                "item:copper-cable tier=4",
+                "item:copper-ore natural tier=0",
+                "item:copper-plate tier=2",
+                "item:electronic-circuit tier=6",
+                "item:iron-ore natural tier=0",
+                "item:iron-plate tier=2",
+                "recipe:copper-cable tier=3",
+                "recipe:copper-plate tier=1",
+                "recipe:electronic-circuit tier=5",
+                "recipe:iron-plate tier=1",
+                "item:copper-cable -> recipe:electronic-circuit 3",
+                "item:copper-ore -> recipe:copper-plate 1",
+                "item:copper-plate -> recipe:copper-cable 1",
+                "item:iron-ore -> recipe:iron-plate 1",
+                "item:iron-plate -> recipe:electronic-circuit 1",
+                "recipe:copper-cable -> item:copper-cable 2",
+                "recipe:copper-plate -> item:copper-plate 1",
+                "recipe:electronic-circuit -> item:electronic-circuit 1",
+                "recipe:iron-plate -> item:iron-plate 1",
+            ]
+            .join("\n")
+        );
+
+        data.recipes.reverse();
+        data.items.reverse();
+        assert_eq!(CraftingGraph::from_dataset(&data).to_snapshot(), snapshot);
+    }
+
+    #[test]
+    fn test_is_degenerate() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        assert!(!graph.is_degenerate());
+
+        let isolated = graph.focus(&[]);
+        assert!(isolated.is_degenerate());
+        assert!(isolated.to_dot().starts_with("digraph {"));
+
+        let mut without_edges = graph.clone();
+        without_edges.data.clear_edges();
+        assert!(without_edges.is_degenerate());
+    }
+
+    #[test]
+    fn test_try_get_nodes() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(
+            graph.try_get_item_node("iron-plate"),
+            Some(graph.get_item_node("iron-plate"))
+        );
+        assert!(matches!(
+            graph.try_get_recipe_node("iron-plate"),
+            Some(Node::Recipe(recipe, _)) if recipe.name == "iron-plate"
+        ));
+        assert_eq!(graph.try_get_item_node("nonexistent"), None);
+        assert_eq!(graph.try_get_recipe_node("iron-ore"), None);
+    }
+
+    #[test]
+    fn test_node_id() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(
+            graph.node_id(graph.get_item_node("iron-plate")),
+            "item:iron-plate"
+        );
+        assert_eq!(
+            graph.node_id(graph.get_recipe_node("electronic-circuit")),
+            "recipe:electronic-circuit"
+        );
+        assert!(graph.to_dot().contains("id = \"item:electronic-circuit\""));
+    }
+
+    #[test]
+    fn test_to_dot_styled_legend() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let plain = graph.to_dot_styled(false);
+        assert!(plain.contains("shape = box, style = filled, fillcolor = \"lightblue\""));
+        assert!(plain.contains("id = \"recipe:copper-cable\", shape = box"));
+        assert!(plain.contains("id = \"item:copper-cable\", shape = ellipse"));
+        assert!(!plain.contains("cluster_legend"));
+
+        let with_legend = graph.to_dot_styled(true);
+        assert!(with_legend.contains("subgraph cluster_legend {"));
+        assert!(with_legend.contains("label = \"oil-refinery\""));
+        assert!(with_legend.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn test_reachability_impact() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "water", "coal"],
+            &[
+                (
+                    "basic-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(45), "petroleum-gas")],
                 ),
-                recipe(
-                    "copper-cable",
-                    0.5,
-                    &[(dec!(1), item("copper-plate"))],
-                    &[(dec!(2), item("copper-cable"))],
-                    FactoryKind::Assembler,
+                (
+                    "advanced-oil-processing",
+                    &[(dec!(100), "crude-oil"), (dec!(50), "water")],
+                    &[(dec!(55), "petroleum-gas"), (dec!(45), "light-oil")],
                 ),
-                recipe(
-                    "iron-plate",
-                    3.2,
-                    &[(dec!(1), item("iron-ore"))],
-                    &[(dec!(1), item("iron-plate"))],
-                    FactoryKind::Assembler,
+                (
+                    "solid-fuel-from-light-oil",
+                    &[(dec!(10), "light-oil")],
+                    &[(dec!(1), "solid-fuel")],
                 ),
-                recipe(
-                    "electronic-circuit",
-                    0.5,
-                    &[
-                        (dec!(3), item("copper-cable")),
-                        (dec!(1), item("iron-plate")),
-                    ],
-                    &[(dec!(1), item("electronic-circuit"))],
-                    FactoryKind::Assembler,
+                (
+                    "plastic-bar",
+                    &[(dec!(20), "petroleum-gas"), (dec!(1), "coal")],
+                    &[(dec!(2), "plastic-bar")],
                 ),
-            ];
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
 
-            Self { recipes, items }
-        }
+        assert!(graph.unreachable_items().is_empty());
+        assert_eq!(
+            graph.reachability_impact("advanced-oil-processing"),
+            vec![data.get_item("light-oil"), data.get_item("solid-fuel")]
+        );
+        assert!(graph.reachability_impact("basic-oil-processing").is_empty());
+        assert!(graph.reachability_impact("coal-liquefaction").is_empty());
     }
 
     #[test]
-    fn test_crafting_graph_structure() {
+    fn test_reversible_recipe_edges() {
+        let mut data = DataSetMock::from_recipes(
+            &["iron-plate"],
+            &[(
+                "iron-gear-wheel",
+                &[(dec!(2), "iron-plate")],
+                &[(dec!(1), "iron-gear-wheel")],
+            )],
+        );
+        data.recipes[0].reversible = true;
+        let reversed = data.recipes[0].reversed().unwrap();
+        data.recipes.push(reversed);
+
+        let plain = CraftingGraph::from_dataset(&data);
+        assert_eq!(plain.recipe_degree("iron-gear-wheel"), Some((1, 1)));
+        assert!(plain
+            .try_get_recipe_node("iron-gear-wheel~reverse")
+            .is_none());
+
+        let graph = CraftingGraph::from_dataset_with_options(
+            &data,
+            GraphOptions {
+                reverse_recipes: true,
+                ..GraphOptions::default()
+            },
+        );
+        let recipe_idx = graph.get_recipe_idx_from_name("iron-gear-wheel").unwrap();
+        let reverse_idx = graph
+            .get_recipe_idx_from_name("iron-gear-wheel~reverse")
+            .unwrap();
+        let plate_idx = graph.get_item_idx_from_name("iron-plate").unwrap();
+        let gear_idx = graph.get_item_idx_from_name("iron-gear-wheel").unwrap();
+
+        assert_eq!(graph.recipe_degree("iron-gear-wheel"), Some((1, 1)));
+        assert_eq!(graph.recipe_degree("iron-gear-wheel~reverse"), Some((1, 1)));
+        assert!(graph.data.contains_edge(plate_idx, recipe_idx));
+        assert!(graph.data.contains_edge(recipe_idx, gear_idx));
+        assert!(graph.data.contains_edge(gear_idx, reverse_idx));
+        assert!(graph.data.contains_edge(reverse_idx, plate_idx));
+        assert!(!graph.data.contains_edge(gear_idx, recipe_idx));
+
+        let tier_of = |idx: NodeIndex| graph.data[idx].get_tier();
+        assert_eq!(
+            [plate_idx, recipe_idx, gear_idx, reverse_idx].map(tier_of),
+            [0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_with_merged_naturals() {
         let data = DataSetMock::new();
+        let raw_ore = Item {
+            name: "raw-ore".to_string(),
+            natural: true,
+        };
         let graph = CraftingGraph::from_dataset(&data);
+        let groups = HashMap::from([(
+            &raw_ore,
+            vec!["iron-ore".to_string(), "copper-ore".to_string()],
+        )]);
+
+        let merged = graph.with_merged_naturals(&groups);
+
+        assert_eq!(merged.data.node_count(), graph.data.node_count() - 1);
+        assert!(merged.get_item_idx_from_name("iron-ore").is_none());
+        assert!(merged.get_item_idx_from_name("copper-ore").is_none());
+
+        let raw_ore = merged.get_item_idx_from_name("raw-ore").unwrap();
+        let consumers = merged
+            .data
+            .neighbors_directed(raw_ore, Direction::Outgoing)
+            .map(|idx| merged.data[idx].to_string())
+            .sorted()
+            .collect_vec();
+        assert_eq!(consumers.len(), 2);
+        assert!(consumers[0].starts_with("copper-plate ("));
+        assert!(consumers[1].starts_with("iron-plate ("));
         assert_eq!(
-            graph.iter_nodes().count(),
-            data.recipes.len() + data.items.len()
+            merged
+                .natural_items
+                .iter()
+                .map(|item| item.name.as_str())
+                .collect_vec(),
+            vec!["raw-ore"]
         );
+    }
 
-        for item in data.iter_items() {
-            assert_eq!(data.try_get_item(&item.name), Some(item));
+    #[test]
+    fn test_factory_kind_distribution() {
+        let mut data = DataSetMock::new();
+        for recipe in &mut data.recipes {
+            if recipe.name.ends_with("-plate") {
+                recipe.factory_kind = FactoryKind::Smelter;
+            }
         }
+        let graph = CraftingGraph::from_dataset(&data);
 
-        for recipe in data.iter_recipes() {
-            assert_eq!(data.try_get_recipe(&recipe.name), Some(recipe));
+        assert_eq!(
+            graph.factory_kind_distribution(),
+            HashMap::from([(FactoryKind::Smelter, 2), (FactoryKind::Assembler, 2)])
+        );
+    }
+
+    #[test]
+    fn test_recipes_up_to_tier() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let names = |tier| {
+            graph
+                .recipes_up_to_tier(tier)
+                .into_iter()
+                .map(|recipe| recipe.name.as_str())
+                .collect_vec()
+        };
+
+        assert!(names(0).is_empty());
+        assert_eq!(names(1), vec!["copper-plate", "iron-plate"]);
+        assert_eq!(
+            names(5),
+            vec![
+                "copper-plate",
+                "iron-plate",
+                "copper-cable",
+                "electronic-circuit"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_mining() {
+        let mut data = DataSetMock::from_recipes(
+            &["iron-ore", "sulfuric-acid"],
+            &[
+                ("iron-ore-mining", &[], &[(dec!(1), "iron-ore")]),
+                (
+                    "uranium-ore-mining",
+                    &[(dec!(1), "sulfuric-acid")],
+                    &[(dec!(1), "uranium-ore")],
+                ),
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+            ],
+        );
+        data.recipes[0].factory_kind = FactoryKind::MiningDrill;
+        data.recipes[1].factory_kind = FactoryKind::MiningDrill;
+
+        let default_graph = CraftingGraph::from_dataset(&data);
+        assert_eq!(
+            default_graph.get_recipe_idx_from_name("iron-ore-mining"),
+            None
+        );
+        assert_eq!(
+            default_graph.get_recipe_idx_from_name("uranium-ore-mining"),
+            None
+        );
+
+        let mining_graph = CraftingGraph::from_dataset_with_options(
+            &data,
+            GraphOptions {
+                include_mining: true,
+                ..GraphOptions::default()
+            },
+        );
+        let iron_ore = mining_graph.get_item_node("iron-ore");
+        assert_eq!(
+            mining_graph.get_recipes_with_item_in_outputs(iron_ore),
+            Some(vec![mining_graph
+                .get_recipe_idx_from_name("iron-ore-mining")
+                .unwrap()])
+        );
+        assert!(mining_graph.get_item_idx_from_name("uranium-ore").is_some());
+
+        let tier_of = |graph: &CraftingGraph, node: Node| {
+            graph.data[graph.get_node_idx(node).unwrap()].get_tier()
+        };
+        assert_eq!(
+            tier_of(&default_graph, default_graph.get_item_node("iron-ore")),
+            0
+        );
+        assert_eq!(
+            tier_of(&default_graph, default_graph.get_recipe_node("iron-plate")),
+            1
+        );
+        assert_eq!(
+            tier_of(
+                &mining_graph,
+                mining_graph.get_recipe_node("iron-ore-mining")
+            ),
+            0
+        );
+        assert_eq!(tier_of(&mining_graph, iron_ore), 1);
+        assert_eq!(
+            tier_of(&mining_graph, mining_graph.get_recipe_node("iron-plate")),
+            2
+        );
+        assert_eq!(
+            tier_of(&mining_graph, mining_graph.get_item_node("iron-plate")),
+            3
+        );
+        assert_eq!(
+            tier_of(&mining_graph, mining_graph.get_item_node("sulfuric-acid")),
+            0
+        );
+        assert_eq!(
+            tier_of(
+                &mining_graph,
+                mining_graph.get_recipe_node("uranium-ore-mining")
+            ),
+            1
+        );
+        assert_eq!(
+            tier_of(&mining_graph, mining_graph.get_item_node("uranium-ore")),
+            2
+        );
+
+        let trees = mining_graph
+            .get_crafting_trees(mining_graph.get_item_node("iron-plate"), 10)
+            .unwrap();
+        assert_eq!(trees.len(), 1);
+        assert!(trees[0]
+            .get_recipe_idx_from_name("iron-ore-mining")
+            .is_some());
+        let trees = default_graph
+            .get_crafting_trees(default_graph.get_item_node("iron-plate"), 10)
+            .unwrap();
+        assert_eq!(trees[0].data.node_count(), 3);
+    }
+
+    #[test]
+    fn test_crafting_trees_rooted_at_recipe() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "lake-water", "ice"],
+            &[
+                (
+                    "water-pumping",
+                    &[(dec!(1), "lake-water")],
+                    &[(dec!(1), "water")],
+                ),
+                ("ice-melting", &[(dec!(1), "ice")], &[(dec!(10), "water")]),
+                (
+                    "advanced-oil-processing",
+                    &[(dec!(50), "water"), (dec!(100), "crude-oil")],
+                    &[
+                        (dec!(25), "heavy-oil"),
+                        (dec!(45), "light-oil"),
+                        (dec!(55), "petroleum-gas"),
+                    ],
+                ),
+                (
+                    "light-oil-cracking",
+                    &[(dec!(30), "water"), (dec!(30), "light-oil")],
+                    &[(dec!(20), "petroleum-gas")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let root = graph.get_recipe_node("advanced-oil-processing");
+
+        let trees = graph.get_crafting_trees(root, 10).unwrap();
+
+        assert_eq!(trees.len(), 2);
+        for tree in &trees {
+            let roots = tree
+                .graph()
+                .externals(Direction::Outgoing)
+                .map(|idx| tree.graph()[idx])
+                .collect_vec();
+            assert_eq!(roots, vec![root]);
+
+            let recipes = tree
+                .iter_nodes()
+                .filter_map(|node| match node {
+                    Node::Recipe(recipe, _) => Some(recipe.name.as_str()),
+                    Node::Item(..) => None,
+                })
+                .sorted()
+                .collect_vec();
+            assert!(
+                recipes == ["advanced-oil-processing", "ice-melting"]
+                    || recipes == ["advanced-oil-processing", "water-pumping"],
+                "Unexpected recipes in tree: {recipes:?}"
+            );
         }
     }
 
@@ -698,15 +3985,20 @@ mod tests {
     fn test_tiers() {
         let data = DataSetMock::new();
         let graph = CraftingGraph::from_dataset(&data);
+        // Naturals are tier 0, a recipe is one past its deepest ingredient and an item one past its
+        // cheapest recipe. The circuit waits for copper cable (tier 4) rather than iron plate
+        // (tier 2), so it ends up at 5, not at the sum of the ingredient tiers.
         let expected_item_tiers: Vec<(&Item, Tier)> = vec![
             (data.get_item("copper-ore"), 0),
             (data.get_item("copper-plate"), 2),
+            (data.get_item("copper-cable"), 4),
             (data.get_item("iron-ore"), 0),
             (data.get_item("iron-plate"), 2),
             (data.get_item("electronic-circuit"), 6),
         ];
         let expected_recipe_tiers: Vec<(&Recipe, Tier)> = vec![
             (data.get_recipe("copper-plate"), 1),
+            (data.get_recipe("copper-cable"), 3),
             (data.get_recipe("iron-plate"), 1),
             (data.get_recipe("electronic-circuit"), 5),
         ];
@@ -0,0 +1,2649 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::LazyLock;
+
+use itertools::Itertools;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef as _;
+use petgraph::Direction;
+use rust_decimal::prelude::ToPrimitive as _;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::entities::{
+    duration_as_decimal, BeltTier, FactoryKind, Fuel, InserterTier, Item, ItemAmount, ItemName,
+    Rate, Recipe, RecipeName,
+};
+
+use super::{CraftingGraph, Node};
+
+/// Modules put in the buildings of a plan, together with the researched productivity bonuses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleConfig {
+    /// Productivity bonus of buildings crafting recipes that allow productivity, e.g. `0.4` for
+    /// four productivity modules 3. Such recipes yield `1 + productivity_bonus` times their net
+    /// results per craft, so fewer crafts (and raw materials) are needed upstream.
+    pub productivity_bonus: Decimal,
+    /// Productivity bonus from infinite research per building kind, e.g. `0.2` for
+    /// [`FactoryKind::MiningDrill`] after two levels of mining productivity. Applies to every recipe
+    /// of the kind, whether it allows modules or not, and stacks additively with
+    /// `productivity_bonus`. Kinds which aren't listed get no bonus.
+    pub research_productivity: HashMap<FactoryKind, Decimal>,
+}
+
+impl ModuleConfig {
+    /// Factor by which the net results of a single craft of `recipe` are multiplied.
+    pub fn yield_multiplier(&self, recipe: &Recipe) -> Decimal {
+        let module_bonus = if recipe.allows_productivity {
+            self.productivity_bonus
+        } else {
+            Decimal::ZERO
+        };
+        let research_bonus = self
+            .research_productivity
+            .get(&recipe.factory_kind)
+            .copied()
+            .unwrap_or_default();
+
+        Decimal::ONE + module_bonus + research_bonus
+    }
+}
+
+/// Fuel burnt by plans which don't name one, see [`Fuel::default`].
+static DEFAULT_FUEL: LazyLock<Fuel> = LazyLock::new(Fuel::default);
+
+/// Decimal places kept in net amounts, which drops the noise left by repeated divisions.
+const NET_PRECISION: u32 = 20;
+
+/// Buildings needed to run `recipe` at `crafts_per_second`.
+fn machines_for_crafts(recipe: &Recipe, crafts_per_second: Decimal) -> Decimal {
+    let machine_rate = Rate::from_craft(
+        Decimal::ONE,
+        recipe.time,
+        recipe.factory_kind.crafting_speed(),
+    );
+
+    crafts_per_second / machine_rate.per_second()
+}
+
+/// Crafts per second executed by the buildings of a plan.
+fn crafts_from_machines<'data>(
+    machine_counts: &HashMap<&'data Recipe, Decimal>,
+) -> HashMap<&'data Recipe, Decimal> {
+    machine_counts
+        .iter()
+        .map(|(recipe, machines)| {
+            let machine_rate = Rate::from_craft(
+                Decimal::ONE,
+                recipe.time,
+                recipe.factory_kind.crafting_speed(),
+            );
+
+            (*recipe, *machines * machine_rate.per_second())
+        })
+        .collect()
+}
+
+/// Phase of `recipe` within `plan`: 0 without ingredients produced by the plan, otherwise one
+/// past the latest phase producing its ingredients. Producers reached again through a crafting
+/// cycle (via `on_path`) are ignored and recorded in `cut_at`. Such phases depend on the path taken,
+/// so only phases of recipes whose own cycles were the only ones cut are kept in `phases`.
+fn recipe_phase<'data>(
+    recipe: &'data Recipe,
+    plan: &[&'data Recipe],
+    phases: &mut HashMap<&'data Recipe, usize>,
+    on_path: &mut HashSet<&'data Recipe>,
+    cut_at: &mut HashSet<&'data Recipe>,
+) -> usize {
+    if let Some(phase) = phases.get(recipe) {
+        return *phase;
+    }
+    on_path.insert(recipe);
+    let outer_cut_at = std::mem::take(cut_at);
+
+    let producers = plan
+        .iter()
+        .filter(|producer| {
+            producer.results.iter().any(|(_, result)| {
+                recipe
+                    .ingredients
+                    .iter()
+                    .any(|(_, ingredient)| ingredient.name == result.name)
+            })
+        })
+        .copied()
+        .collect_vec();
+    let mut phase = 0;
+    for producer in producers {
+        if on_path.contains(producer) {
+            cut_at.insert(producer);
+        } else {
+            phase = phase.max(recipe_phase(producer, plan, phases, on_path, cut_at) + 1);
+        }
+    }
+
+    on_path.remove(recipe);
+    cut_at.remove(recipe);
+    if cut_at.is_empty() {
+        phases.insert(recipe, phase);
+    }
+    cut_at.extend(outer_cut_at);
+
+    phase
+}
+
+/// Amounts flowing through the graph when a target is produced.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Demand<'data> {
+    /// How many times each recipe is executed.
+    pub(crate) crafts: HashMap<&'data Recipe, Decimal>,
+    /// Gross amount of every item that has to be delivered, the target and intermediates included.
+    pub(crate) items: HashMap<&'data Item, Decimal>,
+    /// Amounts of items which are not crafted by any recipe of the plan. These are natural items,
+    /// and items which could only be produced by going around a crafting cycle.
+    pub(crate) raw: HashMap<&'data Item, Decimal>,
+}
+
+/// Item consumed faster than it's produced in a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanDeficit {
+    pub item: ItemName,
+    pub shortfall: Rate,
+}
+
+/// Plan consuming a given rate of an input item, as returned by [`CraftingGraph::consume_input`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumptionPlan<'data> {
+    /// Buildings of every recipe, like [`CraftingGraph::machine_counts`].
+    pub machine_counts: HashMap<&'data Recipe, Decimal>,
+    /// Target items (or crafts, for a recipe target) per second made from the input.
+    pub target_rate: Decimal,
+    /// Input the plan doesn't consume.
+    pub leftover: Rate,
+}
+
+/// Everything about producing a target at a given rate, as returned by
+/// [`CraftingGraph::production_report`]. Serializes to JSON in one call; maps are keyed by item
+/// name and ordered by it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProductionReport {
+    pub target: String,
+    /// Target items (or crafts, for a recipe target) per second.
+    pub rate: Decimal,
+    /// Recipes of the plan, ordered by name.
+    pub recipes: Vec<RecipePlan>,
+    /// Natural items consumed per second, fuel of burner buildings included.
+    pub raw_materials: BTreeMap<ItemName, Decimal>,
+    /// Byproducts produced per second on top of the target, which have to be sunk or voided.
+    pub surplus: BTreeMap<ItemName, Decimal>,
+    /// Power drawn by all buildings, in kilowatts.
+    pub total_power: Decimal,
+    /// Buildings to place, with the machine count of every recipe rounded up.
+    pub total_machines: usize,
+}
+
+/// Single recipe of a [`ProductionReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecipePlan {
+    pub recipe: RecipeName,
+    pub factory_kind: FactoryKind,
+    pub machines: Decimal,
+    pub crafts_per_second: Decimal,
+}
+
+impl<'data> CraftingGraph<'data> {
+    /// Get how many times each recipe has to be executed to produce `amount` of the target,
+    /// regardless of crafting time or machine speed.
+    /// For [`Node::Item`] target `amount` is the amount of that item, for [`Node::Recipe`]
+    /// it's the amount of crafts of that recipe.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn total_crafts(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+    ) -> Option<HashMap<&'data Recipe, Decimal>> {
+        Some(
+            self.demand(target, amount, &ModuleConfig::default())?
+                .crafts,
+        )
+    }
+
+    /// Get how many times per second each recipe has to be executed to sustain the target at `rate`
+    /// per second, regardless of machine speed, which [`Self::machine_counts`] folds in.
+    /// For [`Node::Item`] target `rate` is in items per second, for [`Node::Recipe`] in crafts
+    /// per second.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn crafts_per_second(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+    ) -> Option<HashMap<&'data Recipe, Decimal>> {
+        Some(self.demand(target, rate, &ModuleConfig::default())?.crafts)
+    }
+
+    /// Get the amounts of natural items consumed to produce `amount` of the target, including coal
+    /// burnt by burner buildings.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn raw_materials(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+    ) -> Option<HashMap<&'data Item, Decimal>> {
+        self.raw_materials_with_modules(target, amount, &ModuleConfig::default())
+    }
+
+    /// Same as [`Self::raw_materials`], with the productivity bonus of `modules` applied to the
+    /// recipes that allow productivity.
+    pub fn raw_materials_with_modules(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+        modules: &ModuleConfig,
+    ) -> Option<HashMap<&'data Item, Decimal>> {
+        self.raw_materials_with_fuel(target, amount, modules, &DEFAULT_FUEL)
+    }
+
+    /// Same as [`Self::raw_materials_with_modules`], with burner buildings
+    /// ([`FactoryKind::is_burner`]) burning `fuel` instead of coal. Raw materials always include
+    /// the fuel burnt by the plan. It's reported as the graph's item of the same name when there
+    /// is one, otherwise as `fuel.item`.
+    pub fn raw_materials_with_fuel(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+        modules: &ModuleConfig,
+        fuel: &'data Fuel,
+    ) -> Option<HashMap<&'data Item, Decimal>> {
+        let demand = self.demand(target, amount, modules)?;
+        let mut raw = demand.raw;
+
+        let fuel_item = self
+            .get_item_idx_from_name(&fuel.item.name)
+            .and_then(|idx| match self.data[idx] {
+                Node::Item(item, _) => Some(item),
+                Node::Recipe(..) => None,
+            })
+            .unwrap_or(&fuel.item);
+        for (recipe, crafts) in demand.crafts {
+            if let Some((_, per_craft)) =
+                recipe.factory_kind.fuel_consumption_with(recipe.time, fuel)
+            {
+                *raw.entry(fuel_item).or_default() += per_craft * crafts;
+            }
+        }
+
+        Some(raw)
+    }
+
+    /// Get the natural items consumed per single unit of the target, i.e. [`Self::raw_materials`]
+    /// for an amount of 1. Raw materials scale linearly, so multiplying this by any amount gives
+    /// the raw materials for that amount.
+    /// Returns None if target doesn't exist in graph or can't be crafted from natural resources.
+    pub fn marginal_raw_cost(&self, target: Node<'data>) -> Option<HashMap<&'data Item, Decimal>> {
+        let target_idx = self.get_node_idx(target)?;
+        let craftable = match target {
+            Node::Item(..) => self.item_depths().contains_key(&target_idx),
+            Node::Recipe(recipe, _) => self.recipe_depth(&recipe.name).is_some(),
+        };
+        if !craftable {
+            return None;
+        }
+
+        self.raw_materials(target, Decimal::ONE)
+    }
+
+    /// Format the [`Self::raw_materials`] needed to produce the target at `rate` per second as a
+    /// single line like `copper-ore: 30.0/s, iron-ore: 45.0/s`, ordered by item name.
+    /// Rates are rounded to 3 decimal places and always use `.` as the decimal separator.
+    /// If target doesn't exist in graph, an empty string is returned.
+    pub fn raw_summary(&self, target: Node<'data>, rate: Decimal) -> String {
+        self.raw_materials(target, rate)
+            .unwrap_or_default()
+            .into_iter()
+            .sorted_by(|(item1, _), (item2, _)| item1.name.cmp(&item2.name))
+            .map(|(item, amount)| {
+                let mut shown = amount.round_dp(3).normalize();
+                if shown.scale() == 0 {
+                    shown.rescale(1);
+                }
+
+                format!("{}: {shown}/s", item.name)
+            })
+            .join(", ")
+    }
+
+    /// Get the total amount of natural items needed to produce the target at `rate` per second
+    /// (see [`Self::raw_materials`]), minus the credit for the surplus byproducts of the plan (see
+    /// [`Self::net_production`]), each valued at its entry in `byproduct_values` in natural items
+    /// per item. Byproducts without a value earn no credit. The result can be negative when the
+    /// byproducts are worth more than the plan costs.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn net_raw_cost(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+        byproduct_values: &HashMap<&Item, Decimal>,
+    ) -> Option<Decimal> {
+        let raw_cost: Decimal = self.raw_materials(target, rate)?.into_values().sum();
+        let credit: Decimal = self
+            .net_production(target, rate)?
+            .into_iter()
+            .filter(|(_, amount)| *amount > Decimal::ZERO)
+            .filter_map(|(item, amount)| Some(amount * byproduct_values.get(item)?))
+            .sum();
+
+        Some((raw_cost - credit).normalize())
+    }
+
+    /// Split the raw materials needed to produce `amount` of the target between the recipes that
+    /// consume them directly, i.e. show where the raw material budget goes. Every raw material is
+    /// shared between its consumers in proportion to how much of it they take in, so the values sum
+    /// up to the total [`Self::raw_materials`] of the plan, unless the target itself is a raw
+    /// material. This includes items booked as raw because they could only be produced by going
+    /// around a crafting cycle, which their consumers take in only partly raw.
+    /// Recipes consuming no raw materials are left out. If target doesn't exist in graph, nothing
+    /// is returned.
+    pub fn cost_attribution(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+    ) -> HashMap<&'data Recipe, Decimal> {
+        let Some(demand) = self.demand(target, amount, &ModuleConfig::default()) else {
+            return HashMap::new();
+        };
+
+        let mut consumed: HashMap<&Item, HashMap<&'data Recipe, Decimal>> = HashMap::new();
+        for (recipe, crafts) in &demand.crafts {
+            for (per_craft, item) in self.recipe_ingredients(recipe) {
+                if demand.raw.contains_key(item) {
+                    *consumed
+                        .entry(item)
+                        .or_default()
+                        .entry(*recipe)
+                        .or_default() += per_craft * *crafts;
+                }
+            }
+        }
+
+        let mut attribution: HashMap<&'data Recipe, Decimal> = HashMap::new();
+        for (item, raw_amount) in &demand.raw {
+            let Some(consumers) = consumed.get(item) else {
+                continue;
+            };
+            let total_consumed: Decimal = consumers.values().sum();
+            if total_consumed.is_zero() {
+                continue;
+            }
+
+            let raw_share = *raw_amount / total_consumed;
+            for (recipe, consumed_amount) in consumers {
+                *attribution.entry(*recipe).or_default() += *consumed_amount * raw_share;
+            }
+        }
+
+        attribution.retain(|_, cost| !cost.is_zero());
+        attribution
+    }
+
+    /// For every item with several recipes, pick the recipe needing the least natural items per
+    /// unit of the item, all natural items weighing the same. Ingredients are costed through their
+    /// own cheapest recipes, ties are broken by recipe name.
+    /// Alternatives which could only be costed by going around a crafting cycle are skipped.
+    pub fn cheapest_recipes_by_raw(&self) -> HashMap<&'data Item, &'data Recipe> {
+        let mut memo = HashMap::new();
+
+        self.data
+            .node_indices()
+            .filter_map(|idx| {
+                let Node::Item(item, _) = self.data[idx] else {
+                    return None;
+                };
+                let recipes = self.get_recipes_with_item_in_outputs(self.data[idx])?;
+                if recipes.len() < 2 {
+                    return None;
+                }
+
+                recipes
+                    .into_iter()
+                    .filter_map(|recipe_idx| {
+                        let cost = self.recipe_raw_cost(
+                            recipe_idx,
+                            idx,
+                            &mut memo,
+                            &mut HashSet::from([idx]),
+                            &mut HashSet::new(),
+                        )?;
+                        match self.data[recipe_idx] {
+                            Node::Recipe(recipe, _) => Some((cost, recipe)),
+                            Node::Item(..) => None,
+                        }
+                    })
+                    .min_by(|(cost1, recipe1), (cost2, recipe2)| {
+                        cost1
+                            .cmp(cost2)
+                            .then_with(|| recipe1.name.cmp(&recipe2.name))
+                    })
+                    .map(|(_, recipe)| (item, recipe))
+            })
+            .collect()
+    }
+
+    /// Get the kinds of buildings needed to produce the target by the recipes picked for it in
+    /// [`Self::total_crafts`]. A natural target needs none.
+    /// If target doesn't exist in graph or can't be crafted from natural items, None is returned.
+    pub fn required_factory_kinds(&self, target: Node<'data>) -> Option<HashSet<FactoryKind>> {
+        let target_idx = self.get_node_idx(target)?;
+        let unreachable = self.unreachable_items();
+        let blocked = |idx: NodeIndex| match self.data[idx] {
+            Node::Item(item, _) => unreachable.contains(&item),
+            Node::Recipe(..) => false,
+        };
+        let target_blocked = match target {
+            Node::Item(..) => blocked(target_idx),
+            Node::Recipe(..) => self
+                .data
+                .neighbors_directed(target_idx, Direction::Incoming)
+                .any(blocked),
+        };
+        if target_blocked {
+            return None;
+        }
+
+        Some(
+            self.total_crafts(target, Decimal::ONE)?
+                .into_keys()
+                .map(|recipe| recipe.factory_kind.clone())
+                .collect(),
+        )
+    }
+
+    /// Get how many distinct kinds of buildings the target needs, see
+    /// [`Self::required_factory_kinds`]. A rough measure of how complex the production line is.
+    pub fn distinct_building_count(&self, target: Node<'data>) -> Option<usize> {
+        Some(self.required_factory_kinds(target)?.len())
+    }
+
+    /// Group the recipes picked for the target in [`Self::total_crafts`] into build phases: phase 0
+    /// recipes only need natural items, recipes of every later phase need outputs of the previous
+    /// phases, at least one of them from the phase right before. Recipes inside a phase are ordered
+    /// by name.
+    /// If target doesn't exist in graph, nothing is returned.
+    pub fn build_phases(&self, target: Node<'data>) -> Vec<Vec<&'data Recipe>> {
+        let Some(crafts) = self.total_crafts(target, Decimal::ONE) else {
+            return vec![];
+        };
+        let plan = crafts.into_keys().collect_vec();
+
+        let mut phases = HashMap::new();
+        for recipe in &plan {
+            recipe_phase(
+                recipe,
+                &plan,
+                &mut phases,
+                &mut HashSet::new(),
+                &mut HashSet::new(),
+            );
+        }
+
+        phases
+            .into_iter()
+            .into_group_map_by(|(_, phase)| *phase)
+            .into_iter()
+            .sorted_by_key(|(phase, _)| *phase)
+            .map(|(_, recipes)| {
+                recipes
+                    .into_iter()
+                    .map(|(recipe, _)| recipe)
+                    .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Turn the machine counts of a plan into numbered build steps, one per recipe, with the number
+    /// of buildings rounded up, e.g. `1. Place 4 electric-furnace for iron-plate`. Steps follow
+    /// the build phases (see [`Self::build_phases`]), so every recipe comes after the ones
+    /// supplying its ingredients, and recipes of the same phase are ordered by name. Recipes
+    /// without any building needed are left out.
+    pub fn build_checklist(&self, machine_counts: &HashMap<&'data Recipe, Decimal>) -> Vec<String> {
+        let plan = machine_counts
+            .iter()
+            .filter(|(_, count)| **count > Decimal::ZERO)
+            .map(|(recipe, _)| *recipe)
+            .collect_vec();
+
+        let mut phases = HashMap::new();
+        for recipe in &plan {
+            recipe_phase(
+                recipe,
+                &plan,
+                &mut phases,
+                &mut HashSet::new(),
+                &mut HashSet::new(),
+            );
+        }
+
+        plan.iter()
+            .sorted_by(|recipe1, recipe2| {
+                phases[*recipe1]
+                    .cmp(&phases[*recipe2])
+                    .then_with(|| recipe1.name.cmp(&recipe2.name))
+            })
+            .enumerate()
+            .map(|(step, recipe)| {
+                format!(
+                    "{}. Place {} {} for {}",
+                    step + 1,
+                    machine_counts[*recipe].ceil(),
+                    recipe.factory_kind.building_name(),
+                    recipe.name
+                )
+            })
+            .collect()
+    }
+
+    /// Get the gross amount of every item, natural, intermediate and the target itself, that has
+    /// to be delivered to produce `amount` of the target.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn bill_of_materials(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+    ) -> Option<HashMap<&'data Item, Decimal>> {
+        self.bill_of_materials_with_modules(target, amount, &ModuleConfig::default())
+    }
+
+    /// Same as [`Self::bill_of_materials`], with the productivity bonus of `modules` applied to
+    /// the recipes that allow productivity.
+    pub fn bill_of_materials_with_modules(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+        modules: &ModuleConfig,
+    ) -> Option<HashMap<&'data Item, Decimal>> {
+        Some(self.demand(target, amount, modules)?.items)
+    }
+
+    /// Get how many of the `numerator` item are delivered per `denominator` item while producing
+    /// the target, based on [`Self::bill_of_materials`], e.g. 3 copper-cable per iron-plate for an
+    /// electronic-circuit.
+    /// Returns None if target doesn't exist in graph or either item isn't part of its bill of
+    /// materials.
+    pub fn item_ratio(
+        &self,
+        numerator: &str,
+        denominator: &str,
+        target: Node<'data>,
+    ) -> Option<Decimal> {
+        let bill = self.bill_of_materials(target, Decimal::ONE)?;
+        let amount_of = |name: &str| {
+            bill.iter()
+                .find(|(item, _)| item.name == name)
+                .map(|(_, amount)| *amount)
+        };
+
+        amount_of(numerator)?.checked_div(amount_of(denominator)?)
+    }
+
+    /// Get how many buildings ([`FactoryKind::crafting_speed`]) of every recipe are needed to
+    /// produce the target at `rate` per second.
+    /// For [`Node::Item`] target `rate` is in items per second, for [`Node::Recipe`] in crafts
+    /// per second.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn machine_counts(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+    ) -> Option<HashMap<&'data Recipe, Decimal>> {
+        self.machine_counts_with_free_kinds(target, rate, &HashSet::new())
+    }
+
+    /// Same as [`Self::machine_counts`], but productivity from `modules` reduces the crafts, and so
+    /// the buildings, needed upstream.
+    pub fn machine_counts_with_modules(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+        modules: &ModuleConfig,
+    ) -> Option<HashMap<&'data Recipe, Decimal>> {
+        Some(
+            self.demand(target, rate, modules)?
+                .crafts
+                .into_iter()
+                .map(|(recipe, crafts)| (recipe, machines_for_crafts(recipe, crafts)))
+                .collect(),
+        )
+    }
+
+    /// Same as [`Self::machine_counts`], but recipes crafted by `free_kinds` are assumed to produce
+    /// instantly, e.g. when smelting is taken care of elsewhere. They are reported with 0
+    /// machines and never throttle the chain: their ingredients are still demanded at the full
+    /// rate from upstream recipes, only the buildings of the free kinds themselves are left out.
+    pub fn machine_counts_with_free_kinds(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+        free_kinds: &HashSet<FactoryKind>,
+    ) -> Option<HashMap<&'data Recipe, Decimal>> {
+        let crafts_per_second = self.crafts_per_second(target, rate)?;
+
+        Some(
+            crafts_per_second
+                .into_iter()
+                .map(|(recipe, crafts)| {
+                    let machines = if free_kinds.contains(&recipe.factory_kind) {
+                        Decimal::ZERO
+                    } else {
+                        machines_for_crafts(recipe, crafts)
+                    };
+
+                    (recipe, machines)
+                })
+                .collect(),
+        )
+    }
+
+    /// Get the highest rate of the target that can be produced with at most `total_machines`
+    /// buildings in total, with the machine count of every recipe rounded up. The rate is one at
+    /// which some recipe's buildings run exactly at full capacity, scaling the balanced ratio of
+    /// [`Self::machine_counts`]. A target needing no buildings at all yields 0.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn max_output_for_machine_budget(
+        &self,
+        target: Node<'data>,
+        total_machines: usize,
+    ) -> Option<Decimal> {
+        let per_unit = self
+            .machine_counts(target, Decimal::ONE)?
+            .into_values()
+            .filter(|machines| *machines > Decimal::ZERO)
+            .collect_vec();
+
+        let machines_needed = |rate: Decimal| -> Decimal {
+            per_unit
+                .iter()
+                .map(|machines| (*machines * rate).round_dp(NET_PRECISION).ceil())
+                .sum()
+        };
+        let budget = Decimal::from(total_machines);
+        let per_unit_total: Decimal = per_unit.iter().sum();
+
+        // No rate past `budget / per_unit_total` fits, which bounds the machine count worth trying
+        // for every recipe, and the machines needed only grow with the rate, so the highest count
+        // which fits is binary-searched below that bound.
+        let max_rate = per_unit
+            .iter()
+            .filter_map(|machines| {
+                let upper = (*machines * budget / per_unit_total)
+                    .ceil()
+                    .to_usize()
+                    .unwrap_or(total_machines)
+                    .min(total_machines);
+                let fits =
+                    |count: usize| machines_needed(Decimal::from(count) / *machines) <= budget;
+
+                let (mut low, mut high) = (0, upper);
+                while low < high {
+                    let mid = low + (high - low).div_ceil(2);
+                    if fits(mid) {
+                        low = mid;
+                    } else {
+                        high = mid - 1;
+                    }
+                }
+
+                (low > 0).then(|| Decimal::from(low) / *machines)
+            })
+            .max()
+            .unwrap_or(Decimal::ZERO);
+
+        Some(max_rate.round_dp(NET_PRECISION).normalize())
+    }
+
+    /// Put together the plan for producing the target at `rate` per second with `modules`: its
+    /// recipes with their buildings and crafts per second, raw materials, byproducts, power and
+    /// building count, ready to be serialized.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn production_report(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+        modules: &ModuleConfig,
+    ) -> Option<ProductionReport> {
+        let demand = self.demand(target, rate, modules)?;
+
+        let machine_counts: HashMap<&Recipe, Decimal> = demand
+            .crafts
+            .iter()
+            .map(|(recipe, crafts)| (*recipe, machines_for_crafts(recipe, *crafts)))
+            .collect();
+        let recipes = demand
+            .crafts
+            .iter()
+            .map(|(recipe, crafts)| RecipePlan {
+                recipe: recipe.name.clone(),
+                factory_kind: recipe.factory_kind.clone(),
+                machines: machine_counts[recipe],
+                crafts_per_second: *crafts,
+            })
+            .sorted_by(|plan1, plan2| plan1.recipe.cmp(&plan2.recipe))
+            .collect_vec();
+
+        let mut net = self.net_flow(&demand.crafts, modules);
+        if let Node::Item(item, _) = target {
+            *net.entry(item).or_default() -= rate;
+        }
+        let surplus = net
+            .into_iter()
+            .filter(|(_, amount)| *amount > Decimal::ZERO)
+            .map(|(item, amount)| (item.name.clone(), amount))
+            .collect();
+
+        let raw_materials = self
+            .raw_materials_with_modules(target, rate, modules)?
+            .into_iter()
+            .map(|(item, amount)| (item.name.clone(), amount))
+            .collect();
+
+        Some(ProductionReport {
+            target: target.name().to_string(),
+            rate,
+            total_power: self.total_power(&machine_counts),
+            total_machines: machine_counts
+                .values()
+                .map(|machines| machines.ceil().to_usize().unwrap_or(usize::MAX))
+                .sum(),
+            recipes,
+            raw_materials,
+            surplus,
+        })
+    }
+
+    /// Get recipes of the target's plan which are no longer needed once `imported_item` is
+    /// delivered from outside instead of being crafted: the recipes producing it and their
+    /// ancestors, as long as nothing else in the plan still needs them. Recipes are ordered by name.
+    /// If target doesn't exist in graph, nothing is returned.
+    pub fn recipes_obviated_by_import(
+        &self,
+        target: Node<'data>,
+        imported_item: &str,
+    ) -> Vec<&'data Recipe> {
+        let Some(before) = self.total_crafts(target, Decimal::ONE) else {
+            return vec![];
+        };
+
+        let imported = self.with_imported(imported_item);
+        let after = imported
+            .total_crafts(target, Decimal::ONE)
+            .unwrap_or_default();
+
+        before
+            .into_keys()
+            .filter(|recipe| !after.contains_key(recipe))
+            .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+            .collect()
+    }
+
+    /// Size the plan of the `toward` target to consume `rate` per second of the `input` item, which
+    /// is delivered from outside instead of being crafted: forward planning from a resource rather
+    /// than backward from a demand. Input the plan has no use for is reported as leftover, which
+    /// only happens when the target doesn't need the input at all.
+    /// If the input or the target doesn't exist in graph, then None is returned.
+    pub fn consume_input(
+        &self,
+        input: &str,
+        rate: Decimal,
+        toward: Node<'data>,
+    ) -> Option<ConsumptionPlan<'data>> {
+        self.get_item_idx_from_name(input)?;
+        let imported = self.with_imported(input);
+        let per_unit = imported.demand(toward, Decimal::ONE, &ModuleConfig::default())?;
+
+        let input_per_unit = per_unit
+            .raw
+            .iter()
+            .find(|(item, _)| item.name == input)
+            .map(|(_, amount)| *amount)
+            .unwrap_or_default();
+        if input_per_unit.is_zero() {
+            return Some(ConsumptionPlan {
+                machine_counts: HashMap::new(),
+                target_rate: Decimal::ZERO,
+                leftover: Rate(rate),
+            });
+        }
+
+        let target_rate = rate / input_per_unit;
+        let machine_counts = per_unit
+            .crafts
+            .into_iter()
+            .map(|(recipe, crafts)| (recipe, machines_for_crafts(recipe, crafts * target_rate)))
+            .collect();
+
+        Some(ConsumptionPlan {
+            machine_counts,
+            target_rate: target_rate.round_dp(NET_PRECISION).normalize(),
+            leftover: Rate(Decimal::ZERO),
+        })
+    }
+
+    /// Copy of the graph where nothing produces the item anymore, so plans take it as raw input.
+    fn with_imported(&self, item_name: &str) -> Self {
+        let mut imported = self.clone();
+        let producer_edges = imported
+            .get_item_idx_from_name(item_name)
+            .into_iter()
+            .flat_map(|idx| imported.data.edges_directed(idx, Direction::Incoming))
+            .map(|edge| edge.id())
+            .collect_vec();
+        // Edge removal swaps the last edge into the freed slot, so remove from the highest index.
+        for edge in producer_edges.into_iter().sorted().rev() {
+            imported.data.remove_edge(edge);
+        }
+
+        imported
+    }
+
+    /// Estimate how many items are being crafted at any moment when producing the target at `rate`
+    /// per second, for sizing buffers: every recipe contributes the net results it outputs per
+    /// second times how long a single craft takes in its building (Little's law).
+    /// This assumes buildings never idle and ignores the latency of inserters and belts, as well
+    /// as items waiting in buffers.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn work_in_progress(&self, target: Node<'data>, rate: Decimal) -> Option<Decimal> {
+        let crafts = self.crafts_per_second(target, rate)?;
+
+        Some(
+            crafts
+                .into_iter()
+                .map(|(recipe, crafts)| {
+                    let output_per_craft: Decimal = recipe
+                        .net_results()
+                        .into_iter()
+                        .map(|(amount, _)| amount)
+                        .sum();
+
+                    crafts * duration_as_decimal(recipe.time) * output_per_craft
+                        / recipe.factory_kind.crafting_speed()
+                })
+                .sum::<Decimal>()
+                .normalize(),
+        )
+    }
+
+    /// Measure how much the intermediates of the target's plan are shared between recipes: the
+    /// number of (intermediate, consuming recipe) pairs divided by the number of distinct
+    /// intermediates. Intermediates are the items crafted by the plan other than the target, and
+    /// only recipes of the plan count as consumers. A factor of 1 means every intermediate feeds a
+    /// single recipe, higher means intermediates are reused by several. Plans without
+    /// intermediates have factor 0.
+    /// Consumers are counted rather than weighted by the amounts they demand: a balanced plan
+    /// produces exactly what its recipes consume, so summed intermediate demand over intermediate
+    /// production is 1 for every plan without surplus, and doesn't depend on the production rate
+    /// either.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn reuse_factor(&self, target: Node<'data>) -> Option<Decimal> {
+        let demand = self.demand(target, Decimal::ONE, &ModuleConfig::default())?;
+
+        let intermediates = demand
+            .items
+            .keys()
+            .filter(|item| !demand.raw.contains_key(*item))
+            .filter(|item| !matches!(target, Node::Item(target_item, _) if target_item == **item))
+            .collect_vec();
+        if intermediates.is_empty() {
+            return Some(Decimal::ZERO);
+        }
+
+        let consumer_pairs = intermediates
+            .iter()
+            .map(|item| {
+                demand
+                    .crafts
+                    .keys()
+                    .filter(|recipe| {
+                        recipe
+                            .ingredients
+                            .iter()
+                            .any(|(_, ingredient)| ingredient == **item)
+                    })
+                    .count()
+            })
+            .sum::<usize>();
+
+        Some((Decimal::from(consumer_pairs) / Decimal::from(intermediates.len())).normalize())
+    }
+
+    /// Get the net amount of every item flowing in or out of the factory producing `amount` of the
+    /// target. The requested amount of the target counts as consumed, so in a balanced plan the
+    /// target and intermediates net to zero, natural items are negative and surplus byproducts
+    /// are positive. Items netting to zero are left out.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn net_production(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+    ) -> Option<HashMap<&'data Item, Decimal>> {
+        let demand = self.demand(target, amount, &ModuleConfig::default())?;
+        let mut net = self.net_flow(&demand.crafts, &ModuleConfig::default());
+
+        match target {
+            Node::Item(item, _) => *net.entry(item).or_default() -= amount,
+            Node::Recipe(..) => {}
+        }
+        net.retain(|_, amount| !amount.is_zero());
+
+        Some(net)
+    }
+
+    /// Get recipes whose main product is already in surplus according to `net` (as returned by
+    /// [`Self::net_production`]), so building them would only make the surplus bigger.
+    /// Recipes are ordered by name.
+    pub fn redundant_recipes(&self, net: &HashMap<&'data Item, Decimal>) -> Vec<&'data Recipe> {
+        self.data
+            .node_weights()
+            .filter_map(|node| match node {
+                Node::Recipe(recipe, _) => Some(*recipe),
+                Node::Item(..) => None,
+            })
+            .filter(|recipe| {
+                recipe
+                    .main_product()
+                    .and_then(|product| net.get(product))
+                    .is_some_and(|amount| *amount > Decimal::ZERO)
+            })
+            .unique()
+            .sorted_by(|recipe1, recipe2| recipe1.name.cmp(&recipe2.name))
+            .collect()
+    }
+
+    /// Check that every non-natural item consumed by a plan is also produced by it at least as fast.
+    /// Natural items are assumed to be supplied from outside. On failure every item falling short
+    /// is reported, ordered by name.
+    pub fn verify_plan(
+        &self,
+        machine_counts: &HashMap<&'data Recipe, Decimal>,
+    ) -> Result<(), Vec<PlanDeficit>> {
+        let deficits = self
+            .net_flow(
+                &crafts_from_machines(machine_counts),
+                &ModuleConfig::default(),
+            )
+            .into_iter()
+            .filter(|(item, net)| !item.natural && *net < Decimal::ZERO)
+            .map(|(item, net)| PlanDeficit {
+                item: item.name.clone(),
+                shortfall: Rate(-net),
+            })
+            .sorted_by(|deficit1, deficit2| deficit1.item.cmp(&deficit2.item))
+            .collect_vec();
+
+        if deficits.is_empty() {
+            Ok(())
+        } else {
+            Err(deficits)
+        }
+    }
+
+    /// Get items a plan produces faster than it consumes them, with their surplus per second.
+    /// This is the complement of [`Self::verify_plan`]: byproducts piling up (and the plan's end
+    /// products themselves) have to be sunk or voided by the player.
+    pub fn surplus(
+        &self,
+        machine_counts: &HashMap<&'data Recipe, Decimal>,
+    ) -> HashMap<&'data Item, Decimal> {
+        self.net_flow(
+            &crafts_from_machines(machine_counts),
+            &ModuleConfig::default(),
+        )
+        .into_iter()
+        .filter(|(_, net)| *net > Decimal::ZERO)
+        .collect()
+    }
+
+    /// Get the power drawn by all buildings of a plan, in kilowatts.
+    pub fn total_power(&self, machine_counts: &HashMap<&'data Recipe, Decimal>) -> Decimal {
+        self.power_breakdown(machine_counts)
+            .into_iter()
+            .map(|(_, power)| power)
+            .sum()
+    }
+
+    /// Get the power drawn by buildings of every recipe of a plan, in kilowatts, with the biggest
+    /// consumers first. Recipes drawing the same power are ordered by name.
+    /// Burner buildings ([`FactoryKind::is_burner`]) don't draw electricity and are left out.
+    pub fn power_breakdown(
+        &self,
+        machine_counts: &HashMap<&'data Recipe, Decimal>,
+    ) -> Vec<(&'data Recipe, Decimal)> {
+        machine_counts
+            .iter()
+            .filter(|(recipe, _)| !recipe.factory_kind.is_burner())
+            .map(|(recipe, count)| (*recipe, *count * recipe.factory_kind.power_draw_kw()))
+            .sorted_by(|(recipe1, power1), (recipe2, power2)| {
+                power2
+                    .cmp(power1)
+                    .then_with(|| recipe1.name.cmp(&recipe2.name))
+            })
+            .collect()
+    }
+
+    /// Get the recipe of a plan needing the most buildings, the first place to look at when
+    /// optimizing it. Recipes needing the same number are ordered by name.
+    /// Returns None for an empty plan.
+    pub fn bottleneck_recipe(
+        &self,
+        machine_counts: &HashMap<&'data Recipe, Decimal>,
+    ) -> Option<&'data Recipe> {
+        self.top_n_bottlenecks(machine_counts, 1)
+            .into_iter()
+            .next()
+            .map(|(recipe, _)| recipe)
+    }
+
+    /// Get at most `n` recipes of a plan needing the most buildings, with their machine counts,
+    /// biggest first. Recipes needing the same number are ordered by name.
+    pub fn top_n_bottlenecks(
+        &self,
+        machine_counts: &HashMap<&'data Recipe, Decimal>,
+        n: usize,
+    ) -> Vec<(&'data Recipe, Decimal)> {
+        machine_counts
+            .iter()
+            .map(|(recipe, count)| (*recipe, *count))
+            .sorted_by(|(recipe1, count1), (recipe2, count2)| {
+                count2
+                    .cmp(count1)
+                    .then_with(|| recipe1.name.cmp(&recipe2.name))
+            })
+            .take(n)
+            .collect()
+    }
+
+    /// Get how many inserters of `tier` are needed to load every ingredient into and unload every
+    /// result out of the buildings of each recipe running at the given crafts per second, as
+    /// returned by [`Self::crafts_per_second`]. Keys are pairs of recipe and item names.
+    /// Every building gets its own inserters, so the flow of an item is split across the
+    /// (rounded up) buildings of the recipe before dividing it by [`InserterTier::throughput`].
+    pub fn inserter_requirements(
+        &self,
+        rates: &HashMap<&'data Recipe, Decimal>,
+        tier: InserterTier,
+    ) -> HashMap<(RecipeName, ItemName), usize> {
+        let mut requirements = HashMap::new();
+
+        for (recipe, crafts) in rates {
+            if *crafts <= Decimal::ZERO {
+                continue;
+            }
+
+            let machines = machines_for_crafts(recipe, *crafts).ceil();
+            let flows = recipe
+                .ingredient_map()
+                .into_iter()
+                .chain(recipe.result_map());
+
+            for (item_name, amount) in flows {
+                let per_machine = *crafts * amount / machines;
+                let inserters = machines * (per_machine / tier.throughput()).ceil();
+
+                *requirements
+                    .entry((recipe.name.clone(), item_name.to_string()))
+                    .or_default() += inserters.to_usize().unwrap_or(usize::MAX);
+            }
+        }
+
+        requirements
+    }
+
+    /// Get how many belt lanes of `belt` every bus item of a main bus producing the target at
+    /// `rate` per second needs. Bus items are the ones consumed by more than one recipe of the
+    /// plan, items going into a single recipe are fed to it directly. The lanes of an item carry
+    /// its whole gross flow, see [`BeltTier::lane_throughput`].
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn bus_lanes(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+        belt: BeltTier,
+    ) -> Option<HashMap<&'data Item, usize>> {
+        let demand = self.demand(target, rate, &ModuleConfig::default())?;
+
+        let lanes = demand
+            .items
+            .iter()
+            .filter(|(item, _)| {
+                demand
+                    .crafts
+                    .keys()
+                    .filter(|recipe| recipe.ingredient_map().contains_key(item.name.as_str()))
+                    .count()
+                    > 1
+            })
+            .map(|(item, flow)| {
+                let lanes = (*flow / belt.lane_throughput()).ceil();
+                (*item, lanes.to_usize().unwrap_or(usize::MAX))
+            })
+            .collect();
+
+        Some(lanes)
+    }
+
+    /// Get how many belt lanes of `belt` enter and leave the buildings of every recipe of a plan
+    /// producing the target at `rate` per second, as (input lanes, output lanes). Every item gets
+    /// lanes of its own, see [`BeltTier::lane_throughput`]. More output than input lanes points at
+    /// a stage expanding the flow, like copper-cable from copper-plate, fewer at one compressing
+    /// it.
+    /// If target doesn't exist in graph, then None is returned.
+    pub fn lane_balance(
+        &self,
+        target: Node<'data>,
+        rate: Decimal,
+        belt: BeltTier,
+    ) -> Option<HashMap<&'data Recipe, (usize, usize)>> {
+        let crafts = self.crafts_per_second(target, rate)?;
+        let lanes = |flows: HashMap<&str, Decimal>, crafts: Decimal| -> usize {
+            flows
+                .into_values()
+                .map(|amount| {
+                    (crafts * amount / belt.lane_throughput())
+                        .ceil()
+                        .to_usize()
+                        .unwrap_or(usize::MAX)
+                })
+                .sum()
+        };
+
+        Some(
+            crafts
+                .into_iter()
+                .map(|(recipe, crafts)| {
+                    (
+                        recipe,
+                        (
+                            lanes(recipe.ingredient_map(), crafts),
+                            lanes(recipe.result_map(), crafts),
+                        ),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Propagate the demand for `amount` of the target down to the natural items.
+    /// Every item is crafted by the lowest-tier recipe producing it that doesn't require the item
+    /// itself further down the chain. Demand that could only be satisfied by going around a cycle
+    /// is booked as raw input instead.
+    pub(crate) fn demand(
+        &self,
+        target: Node<'data>,
+        amount: Decimal,
+        modules: &ModuleConfig,
+    ) -> Option<Demand<'data>> {
+        let target_idx = self.get_node_idx(target)?;
+
+        let mut producers = HashMap::new();
+        let mut order = vec![];
+        self.order_by_demand(
+            target_idx,
+            modules,
+            &mut producers,
+            &mut HashSet::new(),
+            &mut HashSet::new(),
+            &mut order,
+        );
+
+        let mut pending: HashMap<NodeIndex, Decimal> = HashMap::from([(target_idx, amount)]);
+        let mut processed = HashSet::new();
+        let mut demand = Demand::default();
+
+        // Reversed post-order visits every consumer before the nodes it depends on, so each node
+        // has its whole demand known by the time it's processed.
+        for idx in order.into_iter().rev() {
+            processed.insert(idx);
+            let Some(requested) = pending.remove(&idx) else {
+                continue;
+            };
+
+            match self.data[idx] {
+                Node::Item(item, _) => {
+                    *demand.items.entry(item).or_default() += requested;
+
+                    match producers.get(&idx) {
+                        Some(&(recipe_idx, net_yield)) => {
+                            *pending.entry(recipe_idx).or_default() += requested / net_yield;
+                        }
+                        None => *demand.raw.entry(item).or_default() += requested,
+                    }
+                }
+                Node::Recipe(recipe, _) => {
+                    *demand.crafts.entry(recipe).or_default() += requested;
+
+                    for edge in self.data.edges_directed(idx, Direction::Incoming) {
+                        let ingredient_idx = edge.source();
+                        let ingredient_amount = *edge.weight() * requested;
+
+                        if processed.contains(&ingredient_idx) {
+                            let Node::Item(item, _) = self.data[ingredient_idx] else {
+                                continue;
+                            };
+                            *demand.items.entry(item).or_default() += ingredient_amount;
+                            *demand.raw.entry(item).or_default() += ingredient_amount;
+                        } else {
+                            *pending.entry(ingredient_idx).or_default() += ingredient_amount;
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(demand)
+    }
+
+    /// Net amount of every item produced (positive) or consumed (negative) by executing each recipe
+    /// the given amount of times, with the productivity bonus of `modules` added to the results.
+    fn net_flow(
+        &self,
+        crafts: &HashMap<&'data Recipe, Decimal>,
+        modules: &ModuleConfig,
+    ) -> HashMap<&'data Item, Decimal> {
+        let mut net: HashMap<&Item, Decimal> = HashMap::new();
+
+        for (recipe, amount) in crafts {
+            let bonus = modules.yield_multiplier(recipe) - Decimal::ONE;
+            for (result_amount, item) in self.recipe_results(recipe) {
+                *net.entry(item).or_default() += result_amount * *amount;
+
+                if !bonus.is_zero() {
+                    let catalyst = recipe
+                        .catalysts
+                        .iter()
+                        .filter(|(_, catalyst_item)| catalyst_item.name == item.name)
+                        .map(|(catalyst_amount, _)| *catalyst_amount)
+                        .sum::<ItemAmount>();
+                    *net.entry(item).or_default() += (result_amount - catalyst) * bonus * *amount;
+                }
+            }
+            for (ingredient_amount, item) in self.recipe_ingredients(recipe) {
+                *net.entry(item).or_default() -= ingredient_amount * *amount;
+            }
+        }
+
+        for amount in net.values_mut() {
+            *amount = amount.round_dp(NET_PRECISION).normalize();
+        }
+
+        net
+    }
+
+    /// Ingredients of the recipe as wired in the graph, which differ from [`Recipe::ingredients`]
+    /// once items are merged, see [`Self::with_merged_naturals`]. Recipes which aren't in the graph
+    /// are taken as they are.
+    fn recipe_ingredients(&self, recipe: &'data Recipe) -> Vec<(ItemAmount, &'data Item)> {
+        self.recipe_flows(recipe, Direction::Incoming)
+            .unwrap_or_else(|| {
+                recipe
+                    .ingredients
+                    .iter()
+                    .map(|(amount, item)| (*amount, item))
+                    .collect()
+            })
+    }
+
+    /// Results of the recipe as wired in the graph, like [`Self::recipe_ingredients`].
+    fn recipe_results(&self, recipe: &'data Recipe) -> Vec<(ItemAmount, &'data Item)> {
+        self.recipe_flows(recipe, Direction::Outgoing)
+            .unwrap_or_else(|| {
+                recipe
+                    .results
+                    .iter()
+                    .map(|(amount, item)| (*amount, item))
+                    .collect()
+            })
+    }
+
+    /// Amounts on the edges between the recipe and its items in `direction`, or None if the recipe
+    /// isn't in the graph.
+    fn recipe_flows(
+        &self,
+        recipe: &Recipe,
+        direction: Direction,
+    ) -> Option<Vec<(ItemAmount, &'data Item)>> {
+        let recipe_idx = self.get_recipe_idx_from_name(&recipe.name)?;
+
+        let flows = self
+            .data
+            .edges_directed(recipe_idx, direction)
+            .filter_map(|edge| {
+                let item_idx = match direction {
+                    Direction::Incoming => edge.source(),
+                    Direction::Outgoing => edge.target(),
+                };
+                match self.data[item_idx] {
+                    Node::Item(item, _) => Some((*edge.weight(), item)),
+                    Node::Recipe(..) => None,
+                }
+            })
+            .collect();
+
+        Some(flows)
+    }
+
+    /// Natural items needed per unit of the item at `item_idx`, crafted by its cheapest recipe.
+    /// None means the item can't be costed without going around a cycle through `on_path`. Items
+    /// skipped that way are recorded in `cut_at`, as costs found without them depend on the path
+    /// taken. Only costs whose own cycles were the only ones cut are memoized.
+    fn item_raw_cost(
+        &self,
+        item_idx: NodeIndex,
+        memo: &mut HashMap<NodeIndex, Option<Decimal>>,
+        on_path: &mut HashSet<NodeIndex>,
+        cut_at: &mut HashSet<NodeIndex>,
+    ) -> Option<Decimal> {
+        match self.data[item_idx] {
+            Node::Item(item, _) if item.natural => return Some(Decimal::ONE),
+            Node::Item(..) => {}
+            Node::Recipe(..) => return None,
+        }
+        if let Some(cost) = memo.get(&item_idx) {
+            return *cost;
+        }
+        if !on_path.insert(item_idx) {
+            cut_at.insert(item_idx);
+            return None;
+        }
+        let outer_cut_at = std::mem::take(cut_at);
+
+        let cost = self
+            .get_recipes_with_item_in_outputs(self.data[item_idx])
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|recipe_idx| {
+                self.recipe_raw_cost(recipe_idx, item_idx, memo, on_path, cut_at)
+            })
+            .min();
+
+        on_path.remove(&item_idx);
+        cut_at.remove(&item_idx);
+        if cut_at.is_empty() {
+            memo.insert(item_idx, cost);
+        }
+        cut_at.extend(outer_cut_at);
+
+        cost
+    }
+
+    /// Natural items needed per unit of the item at `item_idx` when crafted by the recipe at
+    /// `recipe_idx`.
+    fn recipe_raw_cost(
+        &self,
+        recipe_idx: NodeIndex,
+        item_idx: NodeIndex,
+        memo: &mut HashMap<NodeIndex, Option<Decimal>>,
+        on_path: &mut HashSet<NodeIndex>,
+        cut_at: &mut HashSet<NodeIndex>,
+    ) -> Option<Decimal> {
+        let (Node::Recipe(recipe, _), Node::Item(item, _)) =
+            (self.data[recipe_idx], self.data[item_idx])
+        else {
+            return None;
+        };
+        let (net_yield, _) = recipe
+            .net_results()
+            .into_iter()
+            .find(|(amount, result)| result.name == item.name && *amount > Decimal::ZERO)?;
+
+        let ingredients = self
+            .data
+            .edges_directed(recipe_idx, Direction::Incoming)
+            .map(|edge| (edge.source(), *edge.weight()))
+            .collect_vec();
+
+        let mut cost = Decimal::ZERO;
+        for (ingredient_idx, amount) in ingredients {
+            cost += amount * self.item_raw_cost(ingredient_idx, memo, on_path, cut_at)?;
+        }
+
+        Some(cost / net_yield)
+    }
+
+    /// Depth-first walk from `idx` towards the natural items, choosing a producer for every
+    /// non-natural item and pushing nodes in post-order.
+    fn order_by_demand(
+        &self,
+        idx: NodeIndex,
+        modules: &ModuleConfig,
+        producers: &mut HashMap<NodeIndex, (NodeIndex, Decimal)>,
+        on_path: &mut HashSet<NodeIndex>,
+        done: &mut HashSet<NodeIndex>,
+        order: &mut Vec<NodeIndex>,
+    ) {
+        if done.contains(&idx) || on_path.contains(&idx) {
+            return;
+        }
+        on_path.insert(idx);
+
+        match self.data[idx] {
+            Node::Item(item, _) if !item.natural => {
+                let producer = self
+                    .get_recipes_with_item_in_outputs(self.data[idx])
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|recipe_idx| !on_path.contains(recipe_idx))
+                    .find_map(|recipe_idx| match self.data[recipe_idx] {
+                        Node::Recipe(recipe, _) => recipe
+                            .net_results()
+                            .into_iter()
+                            .find(|(amount, result)| {
+                                result.name == item.name && *amount > Decimal::ZERO
+                            })
+                            .map(|(amount, _)| {
+                                (recipe_idx, amount * modules.yield_multiplier(recipe))
+                            }),
+                        Node::Item(..) => None,
+                    });
+
+                if let Some((recipe_idx, net_yield)) = producer {
+                    producers.insert(idx, (recipe_idx, net_yield));
+                    self.order_by_demand(recipe_idx, modules, producers, on_path, done, order);
+                }
+            }
+            Node::Item(..) => {}
+            Node::Recipe(..) => {
+                let ingredients = self
+                    .data
+                    .neighbors_directed(idx, Direction::Incoming)
+                    .collect::<Vec<_>>();
+
+                for ingredient_idx in ingredients {
+                    self.order_by_demand(ingredient_idx, modules, producers, on_path, done, order);
+                }
+            }
+        }
+
+        on_path.remove(&idx);
+        done.insert(idx);
+        order.push(idx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+
+    use itertools::Itertools;
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        data::{DataSet, ParseOptions},
+        domain::{tests::DataSetMock, GraphOptions, Node},
+        entities::FactoryKind,
+        traits::DataSource as _,
+    };
+
+    use crate::entities::{BeltTier, Fuel, InserterTier, Item, Rate};
+
+    use super::{recipe_phase, CraftingGraph, ModuleConfig, PlanDeficit, RecipePlan};
+
+    #[test]
+    fn test_total_crafts() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let crafts = graph
+            .total_crafts(graph.get_item_node("electronic-circuit"), dec!(2))
+            .unwrap();
+
+        assert_eq!(crafts.len(), 4);
+        assert_eq!(crafts[data.get_recipe("electronic-circuit")], dec!(2));
+        assert_eq!(crafts[data.get_recipe("copper-cable")], dec!(3));
+        assert_eq!(crafts[data.get_recipe("copper-plate")], dec!(3));
+        assert_eq!(crafts[data.get_recipe("iron-plate")], dec!(2));
+    }
+
+    #[test]
+    fn test_crafts_per_second() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(2), "iron-gear-wheel")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let gear = graph.get_item_node("iron-gear-wheel");
+
+        let crafts = graph.crafts_per_second(gear, dec!(3)).unwrap();
+
+        assert_eq!(crafts.len(), 2);
+        assert_eq!(crafts[data.get_recipe("iron-gear-wheel")], dec!(1.5));
+        assert_eq!(crafts[data.get_recipe("iron-plate")], dec!(3));
+
+        // Mock recipes take a second in an assembler crafting at 0.75 speed.
+        let machines = graph.machine_counts(gear, dec!(3)).unwrap();
+        assert_eq!(machines[data.get_recipe("iron-gear-wheel")], dec!(2));
+        assert_eq!(machines[data.get_recipe("iron-plate")], dec!(4));
+    }
+
+    #[test]
+    fn test_raw_materials_with_probabilistic_yield() {
+        let recipes = |probability: &str| {
+            format!(
+                r#"{{
+                    "iron-plate": {{
+                        "name": "iron-plate",
+                        "category": "smelting",
+                        "energy": 3.2,
+                        "ingredients": [{{"name": "iron-ore", "amount": 1}}],
+                        "products": [{{"name": "iron-plate", "amount": 1}}]
+                    }},
+                    "lucky-gear": {{
+                        "name": "lucky-gear",
+                        "category": "crafting",
+                        "energy": 0.5,
+                        "ingredients": [{{"name": "iron-plate", "amount": 2}}],
+                        "products": [{{"name": "lucky-gear", "amount": 1, "probability": {probability}}}]
+                    }}
+                }}"#
+            )
+        };
+        let naturals = ["iron-ore".to_string()];
+        let ore_for_gears = |data: &DataSet| {
+            let graph = CraftingGraph::from_dataset(data);
+            let raw = graph
+                .raw_materials(graph.get_item_node("lucky-gear"), dec!(10))
+                .unwrap();
+            let bill = graph
+                .bill_of_materials(graph.get_item_node("lucky-gear"), dec!(10))
+                .unwrap();
+
+            (
+                raw[data.get_item("iron-ore")],
+                bill[data.get_item("iron-plate")],
+            )
+        };
+
+        let certain = DataSet::from_str(&recipes("1"), &naturals).unwrap();
+        let coin_flip = DataSet::from_str(&recipes("0.5"), &naturals).unwrap();
+
+        assert_eq!(ore_for_gears(&certain), (dec!(20), dec!(20)));
+        assert_eq!(ore_for_gears(&coin_flip), (dec!(40), dec!(40)));
+    }
+
+    #[test]
+    fn test_marginal_raw_cost() {
+        let data = DataSetMock::new();
+        let mut graph = CraftingGraph::from_dataset(&data);
+        let circuit = graph.get_item_node("electronic-circuit");
+
+        let per_unit = graph.marginal_raw_cost(circuit).unwrap();
+        assert_eq!(per_unit[data.get_item("copper-ore")], dec!(1.5));
+        assert_eq!(per_unit[data.get_item("iron-ore")], dec!(1));
+
+        let for_ten = graph.raw_materials(circuit, dec!(10)).unwrap();
+        assert!(for_ten
+            .iter()
+            .all(|(item, amount)| *amount == per_unit[item] * dec!(10)));
+
+        graph.remove_recipe("copper-cable");
+        assert_eq!(graph.marginal_raw_cost(circuit), None);
+    }
+
+    #[test]
+    fn test_raw_materials_with_productivity() {
+        let mut data = DataSetMock::new();
+        for recipe in &mut data.recipes {
+            recipe.allows_productivity = recipe.name == "copper-cable";
+        }
+        let graph = CraftingGraph::from_dataset(&data);
+        let target = graph.get_item_node("electronic-circuit");
+
+        let raw = graph.raw_materials(target, dec!(2)).unwrap();
+        assert_eq!(raw[data.get_item("copper-ore")], dec!(3));
+        assert_eq!(raw[data.get_item("iron-ore")], dec!(2));
+
+        let modules = ModuleConfig {
+            productivity_bonus: dec!(0.5),
+            ..ModuleConfig::default()
+        };
+        let raw = graph
+            .raw_materials_with_modules(target, dec!(2), &modules)
+            .unwrap();
+        assert_eq!(raw[data.get_item("copper-ore")], dec!(2));
+        assert_eq!(raw[data.get_item("iron-ore")], dec!(2));
+
+        let bill = graph
+            .bill_of_materials_with_modules(target, dec!(2), &modules)
+            .unwrap();
+        assert_eq!(bill[data.get_item("copper-cable")], dec!(6));
+        assert_eq!(bill[data.get_item("copper-plate")], dec!(2));
+    }
+
+    #[test]
+    fn test_research_productivity() {
+        let mut data = DataSetMock::from_recipes(
+            &["sulfuric-acid"],
+            &[
+                (
+                    "uranium-ore",
+                    &[(dec!(1), "sulfuric-acid")],
+                    &[(dec!(1), "uranium-ore")],
+                ),
+                (
+                    "uranium-processing",
+                    &[(dec!(10), "uranium-ore")],
+                    &[(dec!(10), "uranium-238")],
+                ),
+            ],
+        );
+        data.recipes[0].factory_kind = FactoryKind::MiningDrill;
+        let graph = CraftingGraph::from_dataset_with_options(
+            &data,
+            GraphOptions {
+                include_mining: true,
+                ..GraphOptions::default()
+            },
+        );
+        let target = graph.get_item_node("uranium-238");
+        let mining = data.get_recipe("uranium-ore");
+        let modules = ModuleConfig {
+            productivity_bonus: dec!(0.5),
+            research_productivity: HashMap::from([(FactoryKind::MiningDrill, dec!(0.25))]),
+        };
+
+        let raw = graph.raw_materials(target, dec!(10)).unwrap();
+        assert_eq!(raw[data.get_item("sulfuric-acid")], dec!(10));
+        let raw = graph
+            .raw_materials_with_modules(target, dec!(10), &modules)
+            .unwrap();
+        assert_eq!(raw[data.get_item("sulfuric-acid")], dec!(8));
+
+        // Mock recipes take a second in buildings of the given kind.
+        let speed = FactoryKind::MiningDrill.crafting_speed();
+        let machines = graph
+            .machine_counts_with_modules(target, dec!(10), &modules)
+            .unwrap();
+        assert_eq!(machines[mining], dec!(8) / speed);
+        assert_eq!(
+            graph.machine_counts(target, dec!(10)).unwrap()[mining],
+            dec!(10) / speed
+        );
+    }
+
+    #[test]
+    fn test_raw_summary() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(
+            graph.raw_summary(graph.get_item_node("electronic-circuit"), dec!(0.5)),
+            "copper-ore: 0.75/s, iron-ore: 0.5/s"
+        );
+        assert_eq!(
+            graph.raw_summary(graph.get_item_node("iron-plate"), dec!(2)),
+            "iron-ore: 2.0/s"
+        );
+    }
+
+    #[test]
+    fn test_cost_attribution() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let target = graph.get_item_node("electronic-circuit");
+
+        let attribution = graph.cost_attribution(target, dec!(2));
+
+        assert_eq!(attribution.len(), 2);
+        assert_eq!(attribution[data.get_recipe("copper-plate")], dec!(3));
+        assert_eq!(attribution[data.get_recipe("iron-plate")], dec!(2));
+        assert_eq!(
+            attribution.values().sum::<rust_decimal::Decimal>(),
+            graph.raw_materials(target, dec!(2)).unwrap().values().sum()
+        );
+    }
+
+    #[test]
+    fn test_planning_with_merged_naturals() {
+        let data = DataSetMock::new();
+        let raw_ore = Item {
+            name: "raw-ore".to_string(),
+            natural: true,
+        };
+        let groups = HashMap::from([(
+            &raw_ore,
+            vec!["iron-ore".to_string(), "copper-ore".to_string()],
+        )]);
+        let graph = CraftingGraph::from_dataset(&data).with_merged_naturals(&groups);
+        let target = graph.get_item_node("electronic-circuit");
+
+        assert_eq!(
+            graph.raw_materials(target, dec!(1)),
+            Some(HashMap::from([(&raw_ore, dec!(2.5))]))
+        );
+        assert_eq!(
+            graph.net_production(target, dec!(1)),
+            Some(HashMap::from([(&raw_ore, dec!(-2.5))]))
+        );
+
+        let attribution = graph.cost_attribution(target, dec!(1));
+        assert_eq!(attribution.len(), 2);
+        assert_eq!(attribution[data.get_recipe("copper-plate")], dec!(1.5));
+        assert_eq!(attribution[data.get_recipe("iron-plate")], dec!(1));
+    }
+
+    #[test]
+    fn test_cost_attribution_with_cycle() {
+        let data = DataSetMock::from_recipes(
+            &["ore"],
+            &[
+                (
+                    "seed-loop",
+                    &[(dec!(2), "seed"), (dec!(1), "ore")],
+                    &[(dec!(3), "seed")],
+                ),
+                ("tree", &[(dec!(3), "seed")], &[(dec!(1), "tree")]),
+            ],
+        );
+        // Built directly, as the loop is the only source of seeds, which it also needs, so the
+        // tier computation of `from_dataset` would wait on it forever.
+        let graph = CraftingGraph::from_parts(
+            vec![
+                Node::Item(data.get_item("ore"), 0),
+                Node::Recipe(data.get_recipe("seed-loop"), 1),
+                Node::Item(data.get_item("seed"), 2),
+                Node::Recipe(data.get_recipe("tree"), 3),
+                Node::Item(data.get_item("tree"), 4),
+            ],
+            vec![
+                (0, 1, dec!(1)),
+                (2, 1, dec!(2)),
+                (1, 2, dec!(3)),
+                (2, 3, dec!(3)),
+                (3, 4, dec!(1)),
+            ],
+            vec![data.get_item("ore")],
+        )
+        .unwrap();
+        let target = graph.get_item_node("tree");
+
+        // The loop only yields seeds from seeds, so the 2 seeds it consumes are booked as raw, out
+        // of the 5 seeds consumed in total.
+        let raw = graph.raw_materials(target, dec!(1)).unwrap();
+        assert_eq!(raw[data.get_item("seed")], dec!(2));
+        assert_eq!(raw[data.get_item("ore")], dec!(1));
+
+        let attribution = graph.cost_attribution(target, dec!(1));
+        assert_eq!(attribution.len(), 2);
+        assert_eq!(attribution[data.get_recipe("seed-loop")], dec!(1.8));
+        assert_eq!(attribution[data.get_recipe("tree")], dec!(1.2));
+        assert_eq!(
+            attribution.values().sum::<rust_decimal::Decimal>(),
+            raw.values().sum()
+        );
+    }
+
+    #[test]
+    fn test_cheapest_recipes_by_raw() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "water", "coal"],
+            &[
+                (
+                    "basic-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(45), "petroleum-gas")],
+                ),
+                (
+                    "advanced-oil-processing",
+                    &[(dec!(100), "crude-oil"), (dec!(50), "water")],
+                    &[(dec!(55), "petroleum-gas"), (dec!(45), "light-oil")],
+                ),
+                (
+                    "coal-gasification",
+                    &[(dec!(10), "coal")],
+                    &[(dec!(1), "petroleum-gas")],
+                ),
+                (
+                    "plastic-bar",
+                    &[(dec!(20), "petroleum-gas"), (dec!(1), "coal")],
+                    &[(dec!(2), "plastic-bar")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let cheapest = graph.cheapest_recipes_by_raw();
+
+        // 100 / 45 natural items per unit beats 150 / 55 and 10 / 1
+        assert_eq!(cheapest.len(), 1);
+        assert_eq!(
+            cheapest[data.get_item("petroleum-gas")],
+            data.get_recipe("basic-oil-processing")
+        );
+    }
+
+    #[test]
+    fn test_raw_materials_with_fuel() {
+        let mut data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[(
+                "iron-plate",
+                &[(dec!(1), "iron-ore")],
+                &[(dec!(1), "iron-plate")],
+            )],
+        );
+        data.recipes[0].factory_kind = FactoryKind::BurnerSmelter;
+        data.recipes[0].time = std::time::Duration::from_secs_f64(3.2);
+        let graph = CraftingGraph::from_dataset(&data);
+        let target = graph.get_item_node("iron-plate");
+        let amount_of = |raw: &HashMap<&Item, rust_decimal::Decimal>, name: &str| {
+            raw.iter()
+                .find(|(item, _)| item.name == name)
+                .map(|(_, amount)| *amount)
+        };
+
+        // Neither coal nor wood is part of the graph, the fuel is reported anyway.
+        let raw = graph.raw_materials(target, dec!(10)).unwrap();
+        assert_eq!(raw[data.get_item("iron-ore")], dec!(10));
+        assert_eq!(amount_of(&raw, "coal"), Some(dec!(0.72)));
+
+        let wood = Fuel {
+            item: Item {
+                name: "wood".to_string(),
+                natural: true,
+            },
+            energy_mj: dec!(2),
+        };
+        let raw = graph
+            .raw_materials_with_fuel(target, dec!(10), &ModuleConfig::default(), &wood)
+            .unwrap();
+        assert_eq!(raw[&wood.item], dec!(1.44));
+        assert_eq!(amount_of(&raw, "coal"), None);
+    }
+
+    #[test]
+    fn test_raw_materials_with_burner_buildings() {
+        let recipes = r#"{
+            "iron-plate": {
+                "name": "iron-plate",
+                "category": "smelting",
+                "energy": 3.2,
+                "ingredients": [{"name": "iron-ore", "amount": 1}],
+                "products": [{"name": "iron-plate", "amount": 1}]
+            }
+        }"#;
+        let naturals = ["iron-ore".to_string()];
+        let data = DataSet::from_str_with_options(
+            recipes,
+            &naturals,
+            ParseOptions {
+                burner_buildings: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            data.get_recipe("iron-plate").factory_kind,
+            FactoryKind::BurnerSmelter
+        );
+
+        let graph = CraftingGraph::from_dataset(&data);
+        let raw = graph
+            .raw_materials(graph.get_item_node("iron-plate"), dec!(10))
+            .unwrap();
+        let coal = raw
+            .iter()
+            .find(|(item, _)| item.name == "coal")
+            .map(|(_, amount)| *amount);
+        assert_eq!(coal, Some(dec!(0.72)));
+
+        let electric = DataSet::from_str(recipes, &naturals).unwrap();
+        let graph = CraftingGraph::from_dataset(&electric);
+        let raw = graph
+            .raw_materials(graph.get_item_node("iron-plate"), dec!(10))
+            .unwrap();
+        assert_eq!(raw.len(), 1);
+    }
+
+    #[test]
+    fn test_distinct_building_count() {
+        let mut data = DataSetMock::new();
+        for recipe in &mut data.recipes {
+            if recipe.name.ends_with("-plate") {
+                recipe.factory_kind = FactoryKind::Smelter;
+            }
+        }
+        data.recipes.push(
+            DataSetMock::from_recipes(
+                &[],
+                &[(
+                    "rocket-part",
+                    &[(dec!(10), "rocket-control-unit")],
+                    &[(dec!(1), "rocket-part")],
+                )],
+            )
+            .recipes
+            .remove(0),
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        assert_eq!(
+            graph.distinct_building_count(graph.get_item_node("electronic-circuit")),
+            Some(2)
+        );
+        assert_eq!(
+            graph.distinct_building_count(graph.get_item_node("iron-ore")),
+            Some(0)
+        );
+        assert_eq!(
+            graph.required_factory_kinds(graph.get_item_node("copper-cable")),
+            Some(HashSet::from([
+                FactoryKind::Assembler,
+                FactoryKind::Smelter
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_raw_cost_cut_by_path_is_not_memoized() {
+        let data = DataSetMock::from_recipes(
+            &["ore"],
+            &[
+                ("x-from-ore", &[(dec!(10), "ore")], &[(dec!(1), "x")]),
+                ("x-from-y", &[(dec!(1), "y")], &[(dec!(1), "x")]),
+                ("y-from-x", &[(dec!(1), "x")], &[(dec!(1), "y")]),
+                ("y-from-ore", &[(dec!(5), "ore")], &[(dec!(1), "y")]),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let x = graph.get_item_idx_from_name("x").unwrap();
+        let y = graph.get_item_idx_from_name("y").unwrap();
+        let mut memo = HashMap::new();
+
+        // Crafting x from y is off limits while y is being costed.
+        let cost = graph.item_raw_cost(x, &mut memo, &mut HashSet::from([y]), &mut HashSet::new());
+        assert_eq!(cost, Some(dec!(10)));
+        assert_eq!(memo.get(&x), None);
+
+        let cost = graph.item_raw_cost(x, &mut memo, &mut HashSet::new(), &mut HashSet::new());
+        assert_eq!(cost, Some(dec!(5)));
+        assert_eq!(memo.get(&x), Some(&Some(dec!(5))));
+    }
+
+    #[test]
+    fn test_recipe_phase_cut_by_path_is_not_memoized() {
+        let data = DataSetMock::from_recipes(
+            &["ore"],
+            &[
+                ("a-from-ore", &[(dec!(1), "ore")], &[(dec!(1), "a")]),
+                ("b-from-a", &[(dec!(1), "a")], &[(dec!(1), "b")]),
+                (
+                    "a-from-b",
+                    &[(dec!(1), "b"), (dec!(1), "d")],
+                    &[(dec!(2), "a")],
+                ),
+                ("c-from-ore", &[(dec!(1), "ore")], &[(dec!(1), "c")]),
+                ("d-from-c", &[(dec!(1), "c")], &[(dec!(1), "d")]),
+            ],
+        );
+        let plan = data.recipes.iter().collect_vec();
+        let b_from_a = data.get_recipe("b-from-a");
+        let a_from_b = data.get_recipe("a-from-b");
+        let mut phases = HashMap::new();
+
+        let phase = recipe_phase(
+            b_from_a,
+            &plan,
+            &mut phases,
+            &mut HashSet::from([a_from_b]),
+            &mut HashSet::new(),
+        );
+        assert_eq!(phase, 1);
+        assert_eq!(phases.get(b_from_a), None);
+
+        let phase = recipe_phase(
+            b_from_a,
+            &plan,
+            &mut phases,
+            &mut HashSet::new(),
+            &mut HashSet::new(),
+        );
+        assert_eq!(phase, 3);
+        assert_eq!(phases.get(b_from_a), Some(&3));
+    }
+
+    #[test]
+    fn test_build_phases() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let phases = graph.build_phases(graph.get_item_node("electronic-circuit"));
+
+        assert_eq!(
+            phases,
+            vec![
+                vec![
+                    data.get_recipe("copper-plate"),
+                    data.get_recipe("iron-plate")
+                ],
+                vec![data.get_recipe("copper-cable")],
+                vec![data.get_recipe("electronic-circuit")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_power_breakdown() {
+        let mut data = DataSetMock::new();
+        for recipe in &mut data.recipes {
+            if recipe.name.ends_with("-plate") {
+                recipe.factory_kind = FactoryKind::Smelter;
+            }
+        }
+        let graph = CraftingGraph::from_dataset(&data);
+        let machine_counts = [
+            (data.get_recipe("iron-plate"), dec!(2)),
+            (data.get_recipe("copper-cable"), dec!(3)),
+            (data.get_recipe("electronic-circuit"), dec!(1)),
+        ]
+        .into_iter()
+        .collect();
+
+        let breakdown = graph
+            .power_breakdown(&machine_counts)
+            .into_iter()
+            .map(|(recipe, power)| (recipe.name.as_str(), power))
+            .collect_vec();
+
+        assert_eq!(
+            breakdown,
+            vec![
+                ("copper-cable", dec!(450)),
+                ("iron-plate", dec!(360)),
+                ("electronic-circuit", dec!(150)),
+            ]
+        );
+        assert_eq!(graph.total_power(&machine_counts), dec!(960));
+    }
+
+    #[test]
+    fn test_bus_lanes() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                (
+                    "widget",
+                    &[(dec!(1), "iron-gear-wheel"), (dec!(1), "iron-plate")],
+                    &[(dec!(1), "widget")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let lanes = |belt: BeltTier| {
+            graph
+                .bus_lanes(graph.get_item_node("widget"), dec!(10), belt)
+                .unwrap()
+                .into_iter()
+                .map(|(item, lanes)| (item.name.clone(), lanes))
+                .collect::<HashMap<_, _>>()
+        };
+
+        // 20 plates per second go into gears and another 10 into widgets.
+        assert_eq!(
+            lanes(BeltTier::Basic),
+            HashMap::from([("iron-plate".to_string(), 4)])
+        );
+        assert_eq!(
+            lanes(BeltTier::Express),
+            HashMap::from([("iron-plate".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn test_item_ratio() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let circuit = graph.get_item_node("electronic-circuit");
+
+        assert_eq!(
+            graph.item_ratio("copper-cable", "electronic-circuit", circuit),
+            Some(dec!(3))
+        );
+        assert_eq!(
+            graph.item_ratio("copper-plate", "iron-plate", circuit),
+            Some(dec!(1.5))
+        );
+        assert_eq!(
+            graph.item_ratio(
+                "copper-cable",
+                "iron-plate",
+                graph.get_item_node("copper-cable")
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bottlenecks() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let machine_counts = [
+            (data.get_recipe("iron-plate"), dec!(2)),
+            (data.get_recipe("copper-plate"), dec!(3)),
+            (data.get_recipe("copper-cable"), dec!(3)),
+            (data.get_recipe("electronic-circuit"), dec!(1)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            graph.bottleneck_recipe(&machine_counts),
+            Some(data.get_recipe("copper-cable"))
+        );
+        assert_eq!(
+            graph
+                .top_n_bottlenecks(&machine_counts, 3)
+                .into_iter()
+                .map(|(recipe, count)| (recipe.name.as_str(), count))
+                .collect_vec(),
+            vec![
+                ("copper-cable", dec!(3)),
+                ("copper-plate", dec!(3)),
+                ("iron-plate", dec!(2)),
+            ]
+        );
+        assert_eq!(graph.bottleneck_recipe(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_build_checklist() {
+        let mut data = DataSetMock::new();
+        for recipe in &mut data.recipes {
+            if recipe.name.ends_with("-plate") {
+                recipe.factory_kind = FactoryKind::Smelter;
+            }
+        }
+        let graph = CraftingGraph::from_dataset(&data);
+        let machine_counts = graph
+            .machine_counts(graph.get_item_node("electronic-circuit"), dec!(2))
+            .unwrap();
+
+        assert_eq!(
+            graph.build_checklist(&machine_counts),
+            vec![
+                "1. Place 5 electric-furnace for copper-plate",
+                "2. Place 4 electric-furnace for iron-plate",
+                "3. Place 2 assembling-machine-2 for copper-cable",
+                "4. Place 2 assembling-machine-2 for electronic-circuit",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lane_balance() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let balance = graph
+            .lane_balance(
+                graph.get_item_node("electronic-circuit"),
+                dec!(5),
+                BeltTier::Basic,
+            )
+            .unwrap();
+
+        // 7.5 plates per second turn into 15 cables, a single lane expanding into two.
+        assert_eq!(balance[data.get_recipe("copper-cable")], (1, 2));
+        assert_eq!(balance[data.get_recipe("electronic-circuit")], (3, 1));
+        assert_eq!(balance[data.get_recipe("iron-plate")], (1, 1));
+    }
+
+    #[test]
+    fn test_inserter_requirements() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let rates = graph
+            .crafts_per_second(graph.get_item_node("electronic-circuit"), dec!(1))
+            .unwrap();
+        let needed = |tier: InserterTier, recipe: &str, item: &str| {
+            graph.inserter_requirements(&rates, tier)[&(recipe.to_string(), item.to_string())]
+        };
+
+        // A single circuit assembler takes 3 cables and 1 plate and outputs 1 circuit per second.
+        assert_eq!(
+            needed(InserterTier::Basic, "electronic-circuit", "copper-cable"),
+            4
+        );
+        assert_eq!(
+            needed(InserterTier::Basic, "electronic-circuit", "iron-plate"),
+            2
+        );
+        assert_eq!(
+            needed(
+                InserterTier::Basic,
+                "electronic-circuit",
+                "electronic-circuit"
+            ),
+            2
+        );
+        assert_eq!(
+            needed(InserterTier::Fast, "electronic-circuit", "copper-cable"),
+            2
+        );
+        assert_eq!(
+            needed(InserterTier::Stack, "copper-cable", "copper-cable"),
+            1
+        );
+        assert_eq!(
+            graph
+                .inserter_requirements(&rates, InserterTier::Basic)
+                .len(),
+            9
+        );
+    }
+
+    #[test]
+    fn test_machine_counts_with_free_kinds() {
+        let mut data = DataSetMock::new();
+        for recipe in &mut data.recipes {
+            if recipe.name.ends_with("-plate") {
+                recipe.factory_kind = FactoryKind::Smelter;
+            }
+        }
+        let graph = CraftingGraph::from_dataset(&data);
+        let target = graph.get_item_node("electronic-circuit");
+
+        let counts = graph.machine_counts(target, dec!(1.5)).unwrap();
+        // 1.5 circuits/s * 0.5s / 0.75 speed
+        assert_eq!(counts[data.get_recipe("electronic-circuit")], dec!(1));
+        // 2.25 crafts/s * 0.5s / 0.75 speed
+        assert_eq!(counts[data.get_recipe("copper-cable")], dec!(1.5));
+        // 2.25 crafts/s * 3.2s / 2 speed
+        assert_eq!(counts[data.get_recipe("copper-plate")], dec!(3.6));
+        assert_eq!(counts[data.get_recipe("iron-plate")], dec!(2.4));
+
+        let free_counts = graph
+            .machine_counts_with_free_kinds(
+                target,
+                dec!(1.5),
+                &HashSet::from([FactoryKind::Smelter]),
+            )
+            .unwrap();
+        assert_eq!(free_counts[data.get_recipe("copper-plate")], dec!(0));
+        assert_eq!(free_counts[data.get_recipe("iron-plate")], dec!(0));
+        assert_eq!(free_counts[data.get_recipe("copper-cable")], dec!(1.5));
+    }
+
+    #[test]
+    fn test_redundant_recipes() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil", "coal"],
+            &[
+                (
+                    "advanced-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(25), "heavy-oil"), (dec!(55), "petroleum-gas")],
+                ),
+                (
+                    "coal-liquefaction",
+                    &[(dec!(10), "coal")],
+                    &[(dec!(10), "heavy-oil")],
+                ),
+                (
+                    "solid-fuel",
+                    &[(dec!(20), "petroleum-gas")],
+                    &[(dec!(1), "solid-fuel")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let net = graph
+            .net_production(graph.get_item_node("petroleum-gas"), dec!(110))
+            .unwrap();
+        assert_eq!(net.len(), 2);
+        assert_eq!(net[data.get_item("crude-oil")], dec!(-200));
+        assert_eq!(net[data.get_item("heavy-oil")], dec!(50));
+
+        assert_eq!(
+            graph.redundant_recipes(&net),
+            vec![data.get_recipe("coal-liquefaction")]
+        );
+    }
+
+    #[test]
+    fn test_verify_plan() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let balanced = graph
+            .machine_counts(graph.get_item_node("electronic-circuit"), dec!(1.5))
+            .unwrap();
+
+        assert_eq!(graph.verify_plan(&balanced), Ok(()));
+
+        let mut short_on_cable = balanced.clone();
+        short_on_cable.insert(data.get_recipe("copper-cable"), dec!(1));
+
+        assert_eq!(
+            graph.verify_plan(&short_on_cable),
+            Err(vec![PlanDeficit {
+                item: "copper-cable".to_string(),
+                shortfall: Rate(dec!(1.5)),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_surplus() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil"],
+            &[
+                (
+                    "advanced-oil-processing",
+                    &[(dec!(100), "crude-oil")],
+                    &[(dec!(25), "heavy-oil"), (dec!(55), "petroleum-gas")],
+                ),
+                (
+                    "solid-fuel",
+                    &[(dec!(20), "petroleum-gas")],
+                    &[(dec!(1), "solid-fuel")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let plan = graph
+            .machine_counts(graph.get_item_node("solid-fuel"), dec!(5.5))
+            .unwrap();
+
+        let surplus = graph.surplus(&plan);
+
+        assert_eq!(surplus.len(), 2);
+        assert_eq!(surplus[data.get_item("heavy-oil")], dec!(50));
+        assert_eq!(surplus[data.get_item("solid-fuel")], dec!(5.5));
+        assert!(graph.surplus(&HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_max_output_for_machine_budget() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let gear = graph.get_item_node("iron-gear-wheel");
+
+        // Gears take 4/3 and plates 8/3 machines per gear/s: 1 + 2 machines at 0.75 gear/s,
+        // 2 + 3 machines at 1.125 gear/s.
+        assert_eq!(
+            graph.max_output_for_machine_budget(gear, 4),
+            Some(dec!(0.75))
+        );
+        assert_eq!(
+            graph.max_output_for_machine_budget(gear, 5),
+            Some(dec!(1.125))
+        );
+        assert_eq!(graph.max_output_for_machine_budget(gear, 0), Some(dec!(0)));
+        assert_eq!(
+            graph.max_output_for_machine_budget(gear, 1_000_000),
+            Some(dec!(249999.75))
+        );
+    }
+
+    #[test]
+    fn test_production_report() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate"), (dec!(1), "slag")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        let report = graph
+            .production_report(
+                graph.get_item_node("iron-gear-wheel"),
+                dec!(0.75),
+                &ModuleConfig::default(),
+            )
+            .unwrap();
+
+        assert_eq!(report.target, "iron-gear-wheel");
+        assert_eq!(
+            report.recipes,
+            vec![
+                RecipePlan {
+                    recipe: "iron-gear-wheel".to_string(),
+                    factory_kind: FactoryKind::Assembler,
+                    machines: dec!(1),
+                    crafts_per_second: dec!(0.75),
+                },
+                RecipePlan {
+                    recipe: "iron-plate".to_string(),
+                    factory_kind: FactoryKind::Assembler,
+                    machines: dec!(2),
+                    crafts_per_second: dec!(1.5),
+                },
+            ]
+        );
+        assert_eq!(
+            report.raw_materials,
+            BTreeMap::from([("iron-ore".to_string(), dec!(1.5))])
+        );
+        assert_eq!(
+            report.surplus,
+            BTreeMap::from([("slag".to_string(), dec!(1.5))])
+        );
+        assert_eq!(report.total_power, dec!(450));
+        assert_eq!(report.total_machines, 3);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["recipes"][1]["factory_kind"], "Assembler");
+        assert_eq!(json["surplus"]["slag"], "1.5");
+    }
+
+    #[test]
+    fn test_recipes_obviated_by_import() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let circuit = graph.get_item_node("electronic-circuit");
+
+        assert_eq!(
+            graph.recipes_obviated_by_import(circuit, "iron-plate"),
+            vec![data.get_recipe("iron-plate")]
+        );
+        assert_eq!(
+            graph.recipes_obviated_by_import(circuit, "copper-cable"),
+            vec![
+                data.get_recipe("copper-cable"),
+                data.get_recipe("copper-plate")
+            ]
+        );
+        assert!(graph
+            .recipes_obviated_by_import(circuit, "iron-ore")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_work_in_progress() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        // A second long craft takes 4/3s in an assembler: 0.75 gears/s keep one gear in flight,
+        // the 1.5 plates/s feeding them two plates.
+        assert_eq!(
+            graph.work_in_progress(graph.get_item_node("iron-gear-wheel"), dec!(0.75)),
+            Some(dec!(3))
+        );
+        assert_eq!(
+            graph.work_in_progress(graph.get_item_node("iron-ore"), dec!(1)),
+            Some(dec!(0))
+        );
+    }
+
+    #[test]
+    fn test_reuse_factor() {
+        let data = DataSetMock::from_recipes(
+            &["iron-ore", "copper-ore"],
+            &[
+                (
+                    "iron-plate",
+                    &[(dec!(1), "iron-ore")],
+                    &[(dec!(1), "iron-plate")],
+                ),
+                (
+                    "copper-plate",
+                    &[(dec!(1), "copper-ore")],
+                    &[(dec!(1), "copper-plate")],
+                ),
+                (
+                    "iron-gear-wheel",
+                    &[(dec!(2), "iron-plate")],
+                    &[(dec!(1), "iron-gear-wheel")],
+                ),
+                (
+                    "automation-science-pack",
+                    &[(dec!(1), "copper-plate"), (dec!(1), "iron-gear-wheel")],
+                    &[(dec!(1), "automation-science-pack")],
+                ),
+                (
+                    "inserter-hand",
+                    &[(dec!(1), "iron-plate"), (dec!(1), "iron-gear-wheel")],
+                    &[(dec!(1), "inserter-hand")],
+                ),
+                (
+                    "inserter",
+                    &[
+                        (dec!(1), "inserter-hand"),
+                        (dec!(1), "iron-plate"),
+                        (dec!(1), "automation-science-pack"),
+                    ],
+                    &[(dec!(1), "inserter")],
+                ),
+            ],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+
+        // Gears feed two recipes, iron plates three, the other three intermediates one each.
+        assert_eq!(
+            graph.reuse_factor(graph.get_item_node("inserter")),
+            Some(dec!(1.6))
+        );
+        assert_eq!(
+            graph.reuse_factor(graph.get_item_node("iron-plate")),
+            Some(dec!(0))
+        );
+    }
+
+    #[test]
+    fn test_net_raw_cost() {
+        let data = DataSetMock::from_recipes(
+            &["crude-oil"],
+            &[(
+                "advanced-oil-processing",
+                &[(dec!(100), "crude-oil")],
+                &[
+                    (dec!(25), "heavy-oil"),
+                    (dec!(45), "light-oil"),
+                    (dec!(55), "petroleum-gas"),
+                ],
+            )],
+        );
+        let graph = CraftingGraph::from_dataset(&data);
+        let gas = graph.get_item_node("petroleum-gas");
+
+        assert_eq!(
+            graph.net_raw_cost(gas, dec!(55), &HashMap::new()),
+            Some(dec!(100))
+        );
+        assert_eq!(
+            graph.net_raw_cost(
+                gas,
+                dec!(55),
+                &HashMap::from([(data.get_item("light-oil"), dec!(1))])
+            ),
+            Some(dec!(55))
+        );
+    }
+
+    #[test]
+    fn test_consume_input() {
+        let data = DataSetMock::new();
+        let graph = CraftingGraph::from_dataset(&data);
+        let circuit = graph.get_item_node("electronic-circuit");
+
+        let plan = graph
+            .consume_input("copper-plate", dec!(3), circuit)
+            .unwrap();
+        assert_eq!(plan.target_rate, dec!(2));
+        assert_eq!(plan.leftover, Rate(dec!(0)));
+        assert_eq!(plan.machine_counts.len(), 3);
+        assert!(!plan
+            .machine_counts
+            .contains_key(data.get_recipe("copper-plate")));
+        assert_eq!(
+            plan.machine_counts,
+            graph
+                .machine_counts(circuit, dec!(2))
+                .unwrap()
+                .into_iter()
+                .filter(|(recipe, _)| recipe.name != "copper-plate")
+                .collect()
+        );
+
+        let unused = graph
+            .consume_input("iron-ore", dec!(5), graph.get_item_node("copper-cable"))
+            .unwrap();
+        assert!(unused.machine_counts.is_empty());
+        assert_eq!(unused.leftover, Rate(dec!(5)));
+        assert!(graph.consume_input("unknown", dec!(1), circuit).is_none());
+    }
+}
@@ -1,18 +1,187 @@
+use std::collections::HashMap;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul};
 use std::time::Duration;
 
 use rust_decimal::Decimal;
+use serde::Serialize;
 
 pub type ItemName = String;
 pub type RecipeName = String;
 pub type ItemAmount = Decimal;
 
+/// Throughput of items, in items per second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rate(pub Decimal);
+
+impl Rate {
+    /// Rate at which a building with crafting `speed` outputs `amount` of items, produced by a
+    /// recipe that takes `time` per craft. `time` must not be zero, which holds for every recipe
+    /// parsed by [`crate::data::DataSet`].
+    pub fn from_craft(amount: ItemAmount, time: Duration, speed: Decimal) -> Self {
+        Rate(amount * speed / duration_as_decimal(time))
+    }
+
+    pub fn per_second(&self) -> Decimal {
+        self.0
+    }
+}
+
+impl Add for Rate {
+    type Output = Rate;
+
+    fn add(self, rhs: Rate) -> Rate {
+        Rate(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Rate {
+    fn add_assign(&mut self, rhs: Rate) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Mul<Decimal> for Rate {
+    type Output = Rate;
+
+    fn mul(self, rhs: Decimal) -> Rate {
+        Rate(self.0 * rhs)
+    }
+}
+
+impl Sum for Rate {
+    fn sum<I: Iterator<Item = Rate>>(iter: I) -> Rate {
+        iter.fold(Rate::default(), Add::add)
+    }
+}
+
+/// Convert a duration to seconds without going through floating point.
+pub(crate) fn duration_as_decimal(duration: Duration) -> Decimal {
+    Decimal::from_i128_with_scale(duration.as_nanos() as i128, 9).normalize()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Recipe {
     pub name: RecipeName,
     pub results: Vec<(ItemAmount, Item)>,
+    /// Part of `results` which is returned catalyst rather than net production.
+    pub catalysts: Vec<(ItemAmount, Item)>,
     pub ingredients: Vec<(ItemAmount, Item)>,
     pub time: Duration,
+    /// Crafting category as named in the dump, e.g. `crafting-with-fluid`.
+    pub category: String,
     pub factory_kind: FactoryKind,
+    /// Whether productivity modules can be put in buildings crafting this recipe.
+    pub allows_productivity: bool,
+    /// Whether results can be decomposed back into the ingredients, like recycling in some mods.
+    pub reversible: bool,
+    /// Game version the recipe comes from, for data sets holding several versions side by side.
+    /// None means the recipe is the same in every version.
+    pub version: Option<String>,
+}
+
+/// Suffix of the name of a recipe's inverse, see [`Recipe::reversed`].
+pub const REVERSE_SUFFIX: &str = "~reverse";
+
+impl Recipe {
+    /// Get the inverse of a [`Self::reversible`] recipe, named `<recipe>~reverse`, which turns the
+    /// results back into the ingredients in the same building and time. None for other recipes.
+    pub fn reversed(&self) -> Option<Recipe> {
+        if !self.reversible {
+            return None;
+        }
+
+        Some(Recipe {
+            name: format!("{}{REVERSE_SUFFIX}", self.name),
+            results: self.ingredients.clone(),
+            catalysts: vec![],
+            ingredients: self.results.clone(),
+            time: self.time,
+            category: self.category.clone(),
+            factory_kind: self.factory_kind.clone(),
+            allows_productivity: false,
+            reversible: false,
+            version: self.version.clone(),
+        })
+    }
+
+    /// Whether the recipe is the inverse of another one, as made by [`Self::reversed`].
+    pub fn is_reverse(&self) -> bool {
+        self.name.ends_with(REVERSE_SUFFIX)
+    }
+
+    /// Results of a single craft with the returned catalysts subtracted, i.e. what the recipe really
+    /// adds to the factory. Without catalysts this is the same as `results`.
+    pub fn net_results(&self) -> Vec<(ItemAmount, &Item)> {
+        self.results
+            .iter()
+            .map(|(amount, item)| {
+                let catalyst = self
+                    .catalysts
+                    .iter()
+                    .filter(|(_, catalyst_item)| catalyst_item == item)
+                    .map(|(catalyst_amount, _)| *catalyst_amount)
+                    .sum::<ItemAmount>();
+
+                (*amount - catalyst, item)
+            })
+            .collect()
+    }
+
+    /// Items per second produced by `machines` buildings with crafting `speed` running this recipe,
+    /// with the returned catalysts subtracted like in [`Self::net_results`].
+    pub fn throughput(&self, machines: Decimal, speed: Decimal) -> Vec<(ItemName, Decimal)> {
+        self.net_results()
+            .into_iter()
+            .map(|(amount, item)| {
+                let rate = Rate::from_craft(amount, self.time, speed) * machines;
+
+                (item.name.clone(), rate.per_second())
+            })
+            .collect()
+    }
+
+    /// Energy in kilojoules used by a single craft in a building drawing `power_kw` kilowatts: the
+    /// time the craft spends in the building times the power. `time` is the recipe's duration at
+    /// crafting speed 1, so it's divided by the [`FactoryKind::crafting_speed`] of the recipe's
+    /// building first, otherwise faster buildings would be charged for time they don't run.
+    /// The `_kj` suffix keeps the unit explicit, as `power_kw` is a rate and the result isn't.
+    pub fn energy_per_craft_kj(&self, power_kw: Decimal) -> Decimal {
+        duration_as_decimal(self.time) / self.factory_kind.crafting_speed() * power_kw
+    }
+
+    /// Amount of every ingredient by item name, for lookups without scanning `ingredients`.
+    /// Amounts of an item listed several times are summed.
+    pub fn ingredient_map(&self) -> HashMap<&str, ItemAmount> {
+        amounts_by_name(&self.ingredients)
+    }
+
+    /// Amount of every result by item name, like [`Self::ingredient_map`].
+    pub fn result_map(&self) -> HashMap<&str, ItemAmount> {
+        amounts_by_name(&self.results)
+    }
+
+    /// The item this recipe is meant to produce: its only result, or the result named after the
+    /// recipe. Recipes with several results and none named after them (like oil processing)
+    /// have no main product.
+    pub fn main_product(&self) -> Option<&Item> {
+        match self.results.as_slice() {
+            [(_, item)] => Some(item),
+            results => results
+                .iter()
+                .map(|(_, item)| item)
+                .find(|item| item.name == self.name),
+        }
+    }
+}
+
+fn amounts_by_name(flow: &[(ItemAmount, Item)]) -> HashMap<&str, ItemAmount> {
+    let mut amounts: HashMap<&str, ItemAmount> = HashMap::new();
+    for (amount, item) in flow {
+        *amounts.entry(item.name.as_str()).or_default() += *amount;
+    }
+
+    amounts
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -21,7 +190,7 @@ pub struct Item {
     pub natural: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub enum FactoryKind {
     Assembler,
     OilRefinery,
@@ -29,4 +198,389 @@ pub enum FactoryKind {
     Centrifuge,
     Smelter,
     RocketSilo,
+    MiningDrill,
+    /// Stone furnace, burning fuel instead of drawing electricity.
+    BurnerSmelter,
+    /// Burner mining drill, burning fuel instead of drawing electricity.
+    BurnerMiningDrill,
+}
+
+impl FactoryKind {
+    /// Name of the in-game building used for this kind of factory.
+    pub fn building_name(&self) -> &'static str {
+        match self {
+            FactoryKind::Assembler => "assembling-machine-2",
+            FactoryKind::OilRefinery => "oil-refinery",
+            FactoryKind::ChemicalPlant => "chemical-plant",
+            FactoryKind::Centrifuge => "centrifuge",
+            FactoryKind::Smelter => "electric-furnace",
+            FactoryKind::RocketSilo => "rocket-silo",
+            FactoryKind::MiningDrill => "electric-mining-drill",
+            FactoryKind::BurnerSmelter => "stone-furnace",
+            FactoryKind::BurnerMiningDrill => "burner-mining-drill",
+        }
+    }
+
+    /// Crafting (or mining) speed of the building used for this kind of factory.
+    pub fn crafting_speed(&self) -> Decimal {
+        match self {
+            FactoryKind::Assembler => Decimal::new(75, 2),
+            FactoryKind::Smelter => Decimal::from(2),
+            FactoryKind::MiningDrill => Decimal::new(5, 1),
+            FactoryKind::BurnerMiningDrill => Decimal::new(25, 2),
+            FactoryKind::OilRefinery
+            | FactoryKind::ChemicalPlant
+            | FactoryKind::Centrifuge
+            | FactoryKind::RocketSilo
+            | FactoryKind::BurnerSmelter => Decimal::ONE,
+        }
+    }
+
+    /// Power drawn by a single working building of this kind, in kilowatts.
+    pub fn power_draw_kw(&self) -> Decimal {
+        match self {
+            FactoryKind::Assembler => Decimal::from(150),
+            FactoryKind::OilRefinery => Decimal::from(420),
+            FactoryKind::ChemicalPlant => Decimal::from(210),
+            FactoryKind::Centrifuge => Decimal::from(350),
+            FactoryKind::Smelter => Decimal::from(180),
+            FactoryKind::RocketSilo => Decimal::from(4000),
+            FactoryKind::MiningDrill => Decimal::from(90),
+            FactoryKind::BurnerSmelter => Decimal::from(90),
+            FactoryKind::BurnerMiningDrill => Decimal::from(150),
+        }
+    }
+
+    /// Whether buildings of this kind mine natural items rather than craft them.
+    pub fn is_mining(&self) -> bool {
+        matches!(
+            self,
+            FactoryKind::MiningDrill | FactoryKind::BurnerMiningDrill
+        )
+    }
+
+    /// Whether buildings of this kind burn fuel instead of drawing electricity. Their
+    /// [`Self::power_draw_kw`] is then covered by fuel.
+    pub fn is_burner(&self) -> bool {
+        matches!(
+            self,
+            FactoryKind::BurnerSmelter | FactoryKind::BurnerMiningDrill
+        )
+    }
+
+    /// Amount of [`Fuel::default`] (coal) burnt by a building of this kind during a single craft of
+    /// a recipe taking `recipe_time`. Electric kinds burn nothing, so None is returned for them.
+    pub fn fuel_consumption(&self, recipe_time: Duration) -> Option<(ItemName, Decimal)> {
+        self.fuel_consumption_with(recipe_time, &Fuel::default())
+    }
+
+    /// Same as [`Self::fuel_consumption`], but burning the given `fuel`.
+    pub fn fuel_consumption_with(
+        &self,
+        recipe_time: Duration,
+        fuel: &Fuel,
+    ) -> Option<(ItemName, Decimal)> {
+        if !self.is_burner() {
+            return None;
+        }
+
+        let craft_kj =
+            duration_as_decimal(recipe_time) / self.crafting_speed() * self.power_draw_kw();
+
+        Some((
+            fuel.item.name.clone(),
+            craft_kj / (fuel.energy_mj * Decimal::ONE_THOUSAND),
+        ))
+    }
+}
+
+/// Inserters moving items in and out of buildings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum InserterTier {
+    Burner,
+    Basic,
+    LongHanded,
+    Fast,
+    Stack,
+}
+
+/// In-game name and items moved per second (chest to chest, without capacity research) of every
+/// inserter tier.
+const INSERTER_TIERS: [(InserterTier, &str, Decimal); 5] = [
+    (
+        InserterTier::Burner,
+        "burner-inserter",
+        Decimal::from_parts(6, 0, 0, false, 1),
+    ),
+    (
+        InserterTier::Basic,
+        "inserter",
+        Decimal::from_parts(83, 0, 0, false, 2),
+    ),
+    (
+        InserterTier::LongHanded,
+        "long-handed-inserter",
+        Decimal::from_parts(115, 0, 0, false, 2),
+    ),
+    (
+        InserterTier::Fast,
+        "fast-inserter",
+        Decimal::from_parts(231, 0, 0, false, 2),
+    ),
+    (
+        InserterTier::Stack,
+        "stack-inserter",
+        Decimal::from_parts(462, 0, 0, false, 2),
+    ),
+];
+
+impl InserterTier {
+    fn entry(&self) -> &'static (InserterTier, &'static str, Decimal) {
+        INSERTER_TIERS
+            .iter()
+            .find(|(tier, ..)| tier == self)
+            .expect("Every inserter tier is listed in INSERTER_TIERS")
+    }
+
+    /// Name of the in-game inserter of this tier.
+    pub fn building_name(&self) -> &'static str {
+        self.entry().1
+    }
+
+    /// Items moved per second by a single inserter of this tier.
+    pub fn throughput(&self) -> Decimal {
+        self.entry().2
+    }
+}
+
+/// Transport belts carrying items between buildings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum BeltTier {
+    Basic,
+    Fast,
+    Express,
+}
+
+/// In-game name and items carried per second by both lanes together of every belt tier.
+const BELT_TIERS: [(BeltTier, &str, Decimal); 3] = [
+    (
+        BeltTier::Basic,
+        "transport-belt",
+        Decimal::from_parts(15, 0, 0, false, 0),
+    ),
+    (
+        BeltTier::Fast,
+        "fast-transport-belt",
+        Decimal::from_parts(30, 0, 0, false, 0),
+    ),
+    (
+        BeltTier::Express,
+        "express-transport-belt",
+        Decimal::from_parts(45, 0, 0, false, 0),
+    ),
+];
+
+impl BeltTier {
+    fn entry(&self) -> &'static (BeltTier, &'static str, Decimal) {
+        BELT_TIERS
+            .iter()
+            .find(|(tier, ..)| tier == self)
+            .expect("Every belt tier is listed in BELT_TIERS")
+    }
+
+    /// Name of the in-game belt of this tier.
+    pub fn building_name(&self) -> &'static str {
+        self.entry().1
+    }
+
+    /// Items carried per second by a whole belt of this tier.
+    pub fn throughput(&self) -> Decimal {
+        self.entry().2
+    }
+
+    /// Items carried per second by a single lane, half of the belt.
+    pub fn lane_throughput(&self) -> Decimal {
+        self.throughput() / Decimal::TWO
+    }
+}
+
+/// Fuel burnt by burner buildings ([`FactoryKind::is_burner`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fuel {
+    /// Item burnt, usually natural. Plans report it among their raw materials even when no recipe
+    /// of the graph uses it.
+    pub item: Item,
+    /// Energy released by burning a single item, in megajoules.
+    pub energy_mj: Decimal,
+}
+
+impl Default for Fuel {
+    /// Coal, 4 MJ per item.
+    fn default() -> Self {
+        Fuel {
+            item: Item {
+                name: "coal".to_string(),
+                natural: true,
+            },
+            energy_mj: Decimal::from(4),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rust_decimal_macros::dec;
+
+    use super::{FactoryKind, Fuel, Item, Rate, Recipe};
+
+    #[test]
+    fn test_rate_from_craft() {
+        // Copper cable in an assembling machine 2
+        assert_eq!(
+            Rate::from_craft(dec!(2), Duration::from_secs_f64(0.5), dec!(0.75)),
+            Rate(dec!(3))
+        );
+        // Iron plate in a steel furnace
+        assert_eq!(
+            Rate::from_craft(dec!(1), Duration::from_secs_f64(3.2), dec!(2)),
+            Rate(dec!(0.625))
+        );
+        // Electronic circuit in an assembling machine 1
+        assert_eq!(
+            Rate::from_craft(dec!(1), Duration::from_secs_f64(0.5), dec!(0.5)),
+            Rate(dec!(1))
+        );
+    }
+
+    #[test]
+    fn test_throughput() {
+        let iron_plate = Recipe {
+            name: "iron-plate".to_string(),
+            results: vec![(
+                dec!(1),
+                Item {
+                    name: "iron-plate".to_string(),
+                    natural: false,
+                },
+            )],
+            catalysts: vec![],
+            ingredients: vec![(
+                dec!(1),
+                Item {
+                    name: "iron-ore".to_string(),
+                    natural: true,
+                },
+            )],
+            time: Duration::from_secs_f64(3.2),
+            category: "smelting".to_string(),
+            factory_kind: FactoryKind::Smelter,
+            allows_productivity: true,
+            reversible: false,
+            version: None,
+        };
+
+        // Stone furnaces
+        assert_eq!(
+            iron_plate.throughput(dec!(5), dec!(1)),
+            vec![("iron-plate".to_string(), dec!(1.5625))]
+        );
+        // Electric furnaces
+        assert_eq!(
+            iron_plate.throughput(dec!(5), FactoryKind::Smelter.crafting_speed()),
+            vec![("iron-plate".to_string(), dec!(3.125))]
+        );
+    }
+
+    #[test]
+    fn test_fuel_consumption() {
+        let iron_plate_time = Duration::from_secs_f64(3.2);
+
+        // Stone furnace burns 90kW for 3.2s per plate
+        assert_eq!(
+            FactoryKind::BurnerSmelter.fuel_consumption(iron_plate_time),
+            Some(("coal".to_string(), dec!(0.072)))
+        );
+        assert_eq!(
+            FactoryKind::BurnerSmelter.fuel_consumption_with(
+                iron_plate_time,
+                &Fuel {
+                    item: Item {
+                        name: "wood".to_string(),
+                        natural: true,
+                    },
+                    energy_mj: dec!(2),
+                }
+            ),
+            Some(("wood".to_string(), dec!(0.144)))
+        );
+        assert_eq!(FactoryKind::Smelter.fuel_consumption(iron_plate_time), None);
+    }
+
+    #[test]
+    fn test_ingredient_and_result_maps() {
+        let item = |name: &str| Item {
+            name: name.to_string(),
+            natural: false,
+        };
+        let kovarex = Recipe {
+            name: "kovarex-enrichment-process".to_string(),
+            results: vec![
+                (dec!(41), item("uranium-235")),
+                (dec!(2), item("uranium-238")),
+            ],
+            catalysts: vec![],
+            ingredients: vec![
+                (dec!(40), item("uranium-235")),
+                (dec!(5), item("uranium-238")),
+            ],
+            time: Duration::from_secs(60),
+            category: "centrifuging".to_string(),
+            factory_kind: FactoryKind::Centrifuge,
+            allows_productivity: false,
+            reversible: false,
+            version: None,
+        };
+
+        let ingredients = kovarex.ingredient_map();
+        assert_eq!(ingredients.len(), 2);
+        assert_eq!(ingredients["uranium-235"], dec!(40));
+        assert_eq!(ingredients["uranium-238"], dec!(5));
+        assert_eq!(kovarex.result_map()["uranium-235"], dec!(41));
+        assert!(!kovarex.result_map().contains_key("uranium-ore"));
+    }
+
+    #[test]
+    fn test_energy_per_craft_kj() {
+        let electronic_circuit = Recipe {
+            name: "electronic-circuit".to_string(),
+            results: vec![],
+            catalysts: vec![],
+            ingredients: vec![],
+            time: Duration::from_secs_f64(0.5),
+            category: "crafting".to_string(),
+            factory_kind: FactoryKind::Assembler,
+            allows_productivity: true,
+            reversible: false,
+            version: None,
+        };
+
+        // A craft takes 0.5s / 0.75 in an assembling machine 2 drawing 150kW
+        assert_eq!(
+            electronic_circuit.energy_per_craft_kj(FactoryKind::Assembler.power_draw_kw()),
+            dec!(100)
+        );
+    }
+
+    #[test]
+    fn test_rate_arithmetic() {
+        let total: Rate = [Rate(dec!(1.5)), Rate(dec!(0.5)), Rate(dec!(1))]
+            .into_iter()
+            .sum();
+
+        assert_eq!(total, Rate(dec!(3)));
+        assert_eq!(total * dec!(2), Rate(dec!(6)));
+        assert_eq!(Rate(dec!(1)) + Rate(dec!(0.25)), Rate(dec!(1.25)));
+    }
 }
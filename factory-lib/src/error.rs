@@ -8,12 +8,42 @@ pub enum FactoryError {
     #[error(transparent)]
     Io(#[from] io::Error),
 
-    #[error("Couldn't represent `{0}` as decimal.")]
-    CantRepresentAmountAsDecimal(usize),
+    #[error("Amount `{0}` is neither a number nor a fraction of two numbers.")]
+    InvalidAmount(String),
+
+    #[error(
+        "Recipe `{0}` doesn't have a positive crafting time, so it has no finite crafting rate."
+    )]
+    NonPositiveRecipeTime(String),
+
+    #[error("Edge `{from} -> {to}` points outside of the graph with {node_count} nodes.")]
+    EdgeEndpointOutOfRange {
+        from: usize,
+        to: usize,
+        node_count: usize,
+    },
+
+    #[error("Node `{index}` doesn't exist in the graph with {node_count} nodes.")]
+    NodeIndexOutOfRange { index: usize, node_count: usize },
+
+    #[error("Edge between recipe `{recipe}` and item `{item}` doesn't carry a positive amount.")]
+    NonPositiveAmount { recipe: String, item: String },
+
+    #[error("Item `{0}` is natural in some recipes, but not in others.")]
+    InconsistentNaturalFlag(String),
 
     #[error("Failed to parse provided json file `{0}`")]
     JsonMalformed(serde_json::Error),
 
+    #[error("Failed to parse recipe file `{file}`: {error}")]
+    RecipeFileMalformed {
+        file: String,
+        error: serde_json::Error,
+    },
+
+    #[error("Graphviz `dot` is neither on PATH nor at any of {0:?}")]
+    GraphvizNotFound(Vec<String>),
+
     #[error("Error when spawning command: `{0}`")]
     CommandSpawn(String),
 
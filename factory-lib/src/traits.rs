@@ -1,4 +1,4 @@
-use std::{fs, path::Path};
+use std::{collections::HashSet, fs, path::Path};
 
 use crate::{
     entities::{FactoryKind, Item, Recipe},
@@ -45,6 +45,14 @@ pub trait DataSource {
             .unwrap_or_else(|| panic!("Recipe {name} not found"))
     }
 
+    /// Get every distinct crafting category used by the recipes, handy for finding out which
+    /// categories need an explicit [`FactoryKind`].
+    fn categories(&self) -> HashSet<String> {
+        self.iter_recipes()
+            .map(|recipe| recipe.category.clone())
+            .collect()
+    }
+
     fn category_into_factory_kind(category: &str) -> FactoryKind {
         match category {
             "crafting" | "crafting-with-fluid" | "advanced-crafting" => FactoryKind::Assembler,
@@ -53,6 +61,7 @@ pub trait DataSource {
             "centrifuging" => FactoryKind::Centrifuge,
             "chemistry" => FactoryKind::ChemicalPlant,
             "rocket-building" => FactoryKind::RocketSilo,
+            "mining" => FactoryKind::MiningDrill,
             other => {
                 println!("I encountered other kind of crafting recipe: {other}. Defaulting to regular assembler recipe.");
                 FactoryKind::Assembler